@@ -27,6 +27,7 @@ struct Model {
     grid: Vec<Rect>,
     space: f32,
     animation_phase: f32,
+    show_hud: bool, // fps and the cell under the mouse, H toggles it
 }
 
 fn main() {
@@ -88,6 +89,7 @@ fn model(app: &App) -> Model {
         grid,
         space,
         animation_phase: 0.0,
+        show_hud: false,
     }
 }
 
@@ -117,6 +119,7 @@ fn event(_app: &App, model: &mut Model, event: WindowEvent) {
             model.window_size = size;
             update_model(model);
         }
+        KeyPressed(Key::H) => model.show_hud = !model.show_hud,
         _ => (),
     }
 }
@@ -148,6 +151,59 @@ fn view(app: &App, model: &Model, frame: Frame) {
             .w_h(w, h);
     }
 
+    if model.show_hud {
+        draw_hud(app, model, &draw);
+    }
+
     draw.background().color(BLACK);
     draw.to_frame(app, &frame).unwrap();
 }
+
+// fps, the size of the grid and the row and column of the cell under the mouse
+fn draw_hud(app: &App, model: &Model, draw: &Draw) {
+    let n_rows = (model.window_size.y / model.rectangle_size) as usize;
+    let n_cols = (model.window_size.x / model.rectangle_size) as usize;
+    let mouse = app.mouse.position();
+
+    let mut lines = vec![
+        format!("fps: {:.0}", app.fps()),
+        format!("grid: {} x {}", n_cols, n_rows),
+    ];
+
+    // the grid is laid out column by column
+    if let Some(i) = model.grid.iter().position(|rect| rect.contains(mouse)) {
+        lines.push(format!("row {}, column {}", i % n_rows, i / n_rows));
+
+        let rect = model.grid[i];
+        draw.rect()
+            .xy(rect.xy())
+            .wh(rect.wh())
+            .no_fill()
+            .stroke(WHITE)
+            .stroke_weight(1.5);
+    }
+
+    let window_rect = app.window_rect();
+    let line_height = 16.0;
+    let h = (lines.len() as f32 + 1.0) * line_height;
+    let panel = Rect::from_x_y_w_h(
+        window_rect.left() + 110.0,
+        window_rect.top() - 10.0 - h / 2.0,
+        200.0,
+        h,
+    );
+
+    draw.rect()
+        .xy(panel.xy())
+        .wh(panel.wh())
+        .color(hsva(0.0, 0.0, 0.0, 0.8));
+
+    for (i, line) in lines.iter().enumerate() {
+        draw.text(line)
+            .x_y(panel.x(), panel.top() - (i as f32 + 1.0) * line_height)
+            .w_h(panel.w() - 20.0, line_height)
+            .font_size(12)
+            .left_justify()
+            .color(WHITE);
+    }
+}
@@ -1,6 +1,25 @@
-use nannou::prelude::*;
+use std::fmt;
 
-enum Direction {
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::cell::CellState;
+use crate::chronicle::Chronicle;
+use crate::grid::Grid;
+use crate::journal::Event;
+use crate::path;
+use crate::personality::Traits;
+use crate::rules::{Destinations, RuleRegistry, Transition};
+use crate::topology::CellCoord;
+
+// how strongly explorers follow, or flee, the influence of settled land
+const INFLUENCE_STEERING: f32 = 2.0;
+
+// cells an explorer looks at when picking a destination
+const DESTINATION_CANDIDATES: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Direction {
     Settle,
     Left,
     Right,
@@ -8,69 +27,481 @@ enum Direction {
     Down,
 }
 
+impl Direction {
+    pub const MOVES: [Direction; 4] = [
+        Direction::Left,
+        Direction::Right,
+        Direction::Up,
+        Direction::Down,
+    ];
+
+    pub fn random(rng: &mut (impl Rng + ?Sized)) -> Self {
+        Direction::weighted(rng, [1.0; 4])
+    }
+
+    // pick one of `MOVES`, each as likely as its weight; all zero weights count as equal
+    pub fn weighted(rng: &mut (impl Rng + ?Sized), weights: [f32; 4]) -> Self {
+        let total: f32 = weights.iter().sum();
+        let weights = if total > 0.0 { weights } else { [1.0; 4] };
+        let mut direction = rng.gen::<f32>() * weights.iter().sum::<f32>();
+
+        for (&candidate, &weight) in Direction::MOVES.iter().zip(weights.iter()) {
+            if direction < weight {
+                return candidate;
+            }
+
+            direction -= weight;
+        }
+
+        Direction::Down
+    }
+
+    // rows and columns one step takes
+    pub fn offset(self) -> Option<(isize, isize)> {
+        match self {
+            Direction::Settle => None,
+            Direction::Left => Some((0, -1)),
+            Direction::Right => Some((0, 1)),
+            Direction::Up => Some((1, 0)),
+            Direction::Down => Some((-1, 0)),
+        }
+    }
+}
+
+// handed out by the agent registry, never reused within a run
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct AgentId(pub u32);
+
+impl fmt::Display for AgentId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+// what life rules get to look at when an agent re-decides
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    pub age: u32,             // epochs lived
+    pub epochs_in_state: u32, // epochs since the agent last switched between settling and exploring
+    pub moves: u32,           // times the agent left a settlement to explore
+    #[serde(default)]
+    pub epochs_away: u32, // since the agent last stood on its own land
+}
+
+// where an explorer is headed and the way there
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Route {
+    pub target: CellCoord,
+    remaining: Vec<CellCoord>, // the cells still to step onto, the next one last
+}
+
+impl Route {
+    fn new(target: CellCoord, path: Vec<CellCoord>) -> Self {
+        let mut remaining = path;
+        remaining.reverse();
+
+        Route { target, remaining }
+    }
+
+    // the cells still ahead, in the order they'll be walked
+    pub fn ahead(&self) -> impl Iterator<Item = &CellCoord> {
+        self.remaining.iter().rev()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Agent {
-    pub id: String,
-    pub position: Vec2,
+    pub id: AgentId,
+    pub position: CellCoord,
+    pub history: History,
+    #[serde(default)] // agents saved before traits existed are neutral
+    pub traits: Traits,
+    #[serde(default, skip_serializing_if = "Chronicle::is_empty")]
+    pub chronicle: Chronicle,
+    // not in the journal, so replayed explorers go back to heading in their direction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    route: Option<Route>,
     settle: bool,
     direction: Direction,
     intensity: i32, // how many epochs the agent will remain in the same state
 }
 
 impl Agent {
-    pub fn new(position: Vec2) -> Self {
-        let (settle, direction, intensity) = Self::explore_or_settle();
+    // an agent with random traits, whose first decision already looks at the terrain around it
+    pub fn new(id: AgentId, position: CellCoord, grid: &Grid, rng: &mut impl Rng) -> Self {
+        let traits = Traits::random(rng);
+        Agent::with_traits(id, position, traits, grid, rng)
+    }
+
+    pub fn with_traits(
+        id: AgentId,
+        position: CellCoord,
+        traits: Traits,
+        grid: &Grid,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let mut agent = Agent::with_transition(id, position, Transition::Settle { intensity: 0 });
+        agent.traits = traits;
+
+        agent.apply(agent.explore_or_settle(grid, rng));
+        agent.history = History::default();
+
+        agent
+    }
 
-        Agent {
-            id: format!("{}{}", position.x, position.y),
+    // an agent that starts out with a given decision instead of a random one
+    pub fn with_transition(id: AgentId, position: CellCoord, transition: Transition) -> Self {
+        let mut agent = Agent {
+            id,
             position,
-            settle,
-            direction,
-            intensity,
-        }
+            history: History::default(),
+            traits: Traits::default(),
+            chronicle: Chronicle::new(),
+            route: None,
+            settle: false,
+            direction: Direction::Settle,
+            intensity: 0,
+        };
+
+        agent.apply(transition);
+        agent.history = History::default();
+
+        agent
+    }
+
+    pub fn is_settled(&self) -> bool {
+        self.settle
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn route(&self) -> Option<&Route> {
+        self.route.as_ref()
     }
 
-    // run when intensity reaches zero
-    fn explore_or_settle() -> (bool, Direction, i32) {
-        let settle = random::<bool>();
+    // run when intensity reaches zero and no life rule had anything to say;
+    // attractive land and attachment make settling likelier, restlessness makes trips longer
+    fn explore_or_settle(&self, grid: &Grid, rng: &mut impl Rng) -> Transition {
+        let settle_chance = (grid.cell(self.position).terrain.attractiveness
+            + self.traits.settle_bias())
+        .clamp(0.05, 0.95);
+
+        let settle = rng.gen::<f32>() < settle_chance;
 
-        let direction = if settle {
-            Direction::Settle
+        if settle {
+            Transition::Settle {
+                intensity: rng.gen_range(1..self.traits.max_settle_intensity()),
+            }
         } else {
-            let direction = random::<f32>();
-
-            if direction < 0.25 {
-                Direction::Left
-            } else if direction < 0.5 {
-                Direction::Right
-            } else if direction < 0.75 {
-                Direction::Up
-            } else {
-                Direction::Down
+            let direction = Direction::weighted(rng, self.direction_weights(grid));
+
+            Transition::Explore {
+                direction,
+                intensity: rng.gen_range(1..self.traits.max_explore_intensity()),
             }
+        }
+    }
+
+    // explorers head for attractive, cheap cells, towards or away from other agents' land,
+    // and up or down the influence gradient depending on how sociable they are
+    fn direction_weights(&self, grid: &Grid) -> [f32; 4] {
+        let here = grid.influence(self.position);
+
+        Direction::MOVES.map(|direction| {
+            let (d_row, d_col) = direction.offset().unwrap_or((0, 0));
+
+            let cell = match grid.offset(self.position, d_row, d_col) {
+                Some(coord) if grid.cell(coord).is_passable() => grid.cell(coord),
+                _ => return 0.0,
+            };
+
+            let pull = match cell.owner() {
+                Some(owner) if owner == self.id => self.traits.home_pull(),
+                Some(_) => self.traits.foreign_pull(),
+                None => 1.0,
+            };
+
+            // relative, so it steers as much at the edge of a hamlet as next to a city
+            let there = grid.influence(cell.coord);
+            let gradient = (there - here) / (1.0 + there.max(here));
+            let community = (INFLUENCE_STEERING * self.traits.community_pull() * gradient).exp();
+
+            cell.terrain.pull() * pull * community
+        })
+    }
+
+    // where a child would be born and who it would be, if there's an empty cell next door
+    pub fn offspring(
+        &self,
+        grid: &Grid,
+        mutation: f32,
+        rng: &mut impl Rng,
+    ) -> Option<(CellCoord, Traits)> {
+        let free: Vec<CellCoord> = grid
+            .neighbors(self.position)
+            .into_iter()
+            .filter(|&coord| grid.cell(coord).state == CellState::Empty)
+            .collect();
+
+        if free.is_empty() {
+            return None;
+        }
+
+        let position = free[rng.gen_range(0..free.len())];
+
+        Some((position, self.traits.inherit(mutation, rng)))
+    }
+
+    pub fn apply(&mut self, transition: Transition) {
+        let (settle, direction, intensity) = match transition {
+            Transition::Settle { intensity } => (true, Direction::Settle, intensity),
+            Transition::Explore {
+                direction,
+                intensity,
+            } => (false, direction, intensity),
         };
 
-        let intensity = random_range(1, 10);
+        if settle != self.settle {
+            self.history.epochs_in_state = 0;
+
+            if self.settle {
+                self.history.moves += 1;
+            }
+        }
 
-        (settle, direction, intensity)
+        self.settle = settle;
+        self.direction = direction;
+        self.intensity = intensity;
+        self.route = None;
     }
 
-    // run every epoch
-    // @TODO find a clean way to inject the cells map here
+    // pick a cell within reach of this trip, attractive empty land or, for the sociable, someone
+    // else's, and the cheapest way there; the agent keeps its direction if nothing is found
+    fn head_for_destination(
+        &mut self,
+        grid: &Grid,
+        destinations: Destinations,
+        rng: &mut impl Rng,
+    ) {
+        let radius = destinations.radius.min(self.intensity.max(0) as usize) as isize;
+
+        if radius == 0 {
+            return;
+        }
+
+        let mut candidates: Vec<(CellCoord, f32)> = Vec::new();
+
+        for _ in 0..DESTINATION_CANDIDATES {
+            let d_row = rng.gen_range(-radius..=radius);
+            let d_col = rng.gen_range(-radius..=radius);
+
+            let cell = match grid.offset(self.position, d_row, d_col) {
+                Some(coord)
+                    if coord != self.position
+                        && path::is_open(grid.cell(coord))
+                        && path::distance(grid, self.position, coord) <= radius as usize =>
+                {
+                    grid.cell(coord)
+                }
+                _ => continue,
+            };
+
+            let pull = match cell.owner() {
+                Some(owner) if owner == self.id => continue,
+                Some(_) => self.traits.foreign_pull(),
+                None => 1.0,
+            };
+
+            candidates.push((cell.coord, cell.terrain.pull() * pull));
+        }
+
+        let total: f32 = candidates.iter().map(|&(_, weight)| weight).sum();
+
+        if total <= 0.0 {
+            return;
+        }
+
+        let mut pick = rng.gen::<f32>() * total;
+        let target = candidates
+            .iter()
+            .find(|&&(_, weight)| {
+                pick -= weight;
+                pick < 0.0
+            })
+            .or(candidates.last())
+            .map(|&(coord, _)| coord);
+
+        if let Some(target) = target {
+            self.route =
+                path::find(grid, self.position, target).map(|path| Route::new(target, path));
+        }
+    }
+
+    // run every epoch, everything that changes is pushed to `events`
     // @TODO decide: should agents spawn children agents or paint adjacent cells?
-    pub fn update() {
-        // if direction is settle, expand to neighboring cells
-        // if direction is not settle, move in that direction
-        // if intensity reaches zero, run explore_or_settle
+    pub fn update(
+        &mut self,
+        grid: &mut Grid,
+        rules: &RuleRegistry,
+        rng: &mut impl Rng,
+        events: &mut Vec<Event>,
+    ) {
+        let mut cells = Vec::new();
+        self.act(grid, rng, &mut cells, events);
+
+        for coord in cells {
+            events.extend(grid.fill(coord, self));
+        }
+
+        self.finish(grid, rules, rng, events);
+    }
+
+    // like `update`, but against a grid that doesn't change during the epoch; the cells the
+    // agent would fill are added to `claims` instead of filled
+    pub fn plan(
+        &mut self,
+        grid: &Grid,
+        rules: &RuleRegistry,
+        rng: &mut impl Rng,
+        claims: &mut Vec<CellCoord>,
+        events: &mut Vec<Event>,
+    ) {
+        self.act(grid, rng, claims, events);
+        self.finish(grid, rules, rng, events);
+    }
+
+    // settle or explore, adding the cells to fill to `cells`
+    fn act(
+        &mut self,
+        grid: &Grid,
+        rng: &mut impl Rng,
+        cells: &mut Vec<CellCoord>,
+        events: &mut Vec<Event>,
+    ) {
+        if self.settle {
+            self.settle(grid, cells);
+        } else {
+            self.explore(grid, rng, events);
+        }
     }
 
-    // run when agent is settled
-    pub fn settle() {
-        // paint every neighboring cell
-        // if any neighboring cell is already settled, stop expanding
+    // count the epoch and re-decide once the current decision has run its course
+    fn finish(
+        &mut self,
+        grid: &Grid,
+        rules: &RuleRegistry,
+        rng: &mut impl Rng,
+        events: &mut Vec<Event>,
+    ) {
+        self.intensity -= 1;
+        self.history.age += 1;
+        self.history.epochs_in_state += 1;
+
+        if grid.cell(self.position).owner() == Some(self.id) {
+            self.history.epochs_away = 0;
+        } else {
+            self.history.epochs_away += 1;
+        }
+
+        if self.intensity <= 0 {
+            let transition = match rules.decide(self, grid, rng) {
+                Some(transition) => transition,
+                None => self.explore_or_settle(grid, rng),
+            };
+
+            self.apply(transition);
+            events.push(Event::AgentRedecided {
+                id: self.id,
+                transition,
+            });
+
+            let destinations = rules.destinations;
+
+            if !self.settle && destinations.chance > 0.0 && rng.gen::<f32>() < destinations.chance {
+                self.head_for_destination(grid, destinations, rng);
+            }
+        }
+    }
+
+    // run when agent is settled: its own cell, then the neighboring cells that are less
+    // established than this one, so settlements grow into open land and stop where a stronger
+    // community is felt
+    pub fn settle(&self, grid: &Grid, cells: &mut Vec<CellCoord>) {
+        let here = grid.influence(self.position);
+        cells.push(self.position);
+
+        cells.extend(
+            grid.neighbors(self.position)
+                .into_iter()
+                .filter(|&coord| grid.influence(coord) <= here),
+        );
     }
 
     // run when agent is not settled
-    pub fn explore() {
-        // move in the direction
+    pub fn explore(&mut self, grid: &Grid, rng: &mut impl Rng, events: &mut Vec<Event>) {
+        let next = if self.route.is_some() {
+            self.next_on_route(grid)
+        } else {
+            self.next_in_direction(grid)
+        };
+
+        if let Some(coord) = next {
+            // costly land holds explorers back for a while
+            let cost = grid.cell(coord).terrain.cost;
+
+            if cost > 0.0 && rng.gen::<f32>() < cost {
+                return;
+            }
+
+            events.push(Event::AgentMoved {
+                id: self.id,
+                from: self.position,
+                to: coord,
+            });
+            self.position = coord;
+
+            // arriving ends the trip, so the agent re-decides at the end of this epoch
+            if let Some(route) = &mut self.route {
+                route.remaining.pop();
+
+                if route.remaining.is_empty() {
+                    self.route = None;
+                    self.intensity = 1;
+                }
+            }
+        }
+    }
+
+    // move in the direction, staying put at the edges of a bounded grid and before obstacles
+    fn next_in_direction(&self, grid: &Grid) -> Option<CellCoord> {
+        let (d_row, d_col) = self.direction.offset()?;
+
+        grid.offset(self.position, d_row, d_col)
+            .filter(|&coord| grid.cell(coord).is_passable())
+    }
+
+    // the next cell of the route, re-planned if the way got blocked since; an explorer with no
+    // way left to its destination goes back to heading in its direction
+    fn next_on_route(&mut self, grid: &Grid) -> Option<CellCoord> {
+        let route = self.route.as_mut()?;
+        let clear = route.remaining.last().is_some_and(|&next| {
+            path::is_open(grid.cell(next)) && grid.neighbors(self.position).contains(&next)
+        });
+
+        if !clear {
+            match path::find(grid, self.position, route.target) {
+                Some(path) => *route = Route::new(route.target, path),
+                None => {
+                    self.route = None;
+                    return self.next_in_direction(grid);
+                }
+            }
+        }
+
+        self.route
+            .as_ref()
+            .and_then(|route| route.remaining.last().copied())
     }
 }
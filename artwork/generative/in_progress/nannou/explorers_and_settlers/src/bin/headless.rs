@@ -0,0 +1,306 @@
+// Runs the simulation without a window and prints statistics for every epoch.
+//
+// usage: cargo run --release --bin headless -- <cols> <rows> <epochs> [seed] [topology]
+//        cargo run --release --bin headless -- --from <snapshot.json> <epochs>
+//
+// topology is one of von-neumann, moore or hexagonal, with a -torus suffix to wrap the edges.
+// --from continues a world saved from the sketch (S key) or by --save, so a single
+// interesting moment can be branched into several "what-if" runs.
+// --config <config.toml> uses the agent density, fill cap, terrain map and schedule of the
+// sketch's config; a world continued with --from keeps the fill cap, terrain and schedule it was
+// saved with. `schedule = "parallel"` spreads large grids over every core, but only pays off
+// with several of them: on a single core a 2000x2000 world takes about 0.8 to 1 s per epoch with
+// either schedule. planning, spreading influence, filling and fading land all split across the
+// cores; only recording claimed land, meetings and chronicles stay on one, at about 0.1 s of
+// that. `cargo test --release -- --ignored --nocapture` times it.
+// --save <snapshot.json> writes the final world, and can be combined with either form, but not
+// with a config that has several regions.
+// --journal <journal.jsonl> writes every event of the run, one json entry per line.
+// --metrics <metrics.csv> writes territory clusters and their sizes, ownership entropy and the
+// blocked share for every epoch, --territories <territories.csv> the cells owned by each agent,
+// and --traits <traits.csv> the mean and spread of every personality trait.
+// --graphml <social.graphml> and --dot <social.dot> write who met whom by the end of the run.
+// --chronicles <chronicles.md> writes every agent's life story, as json for a .json path.
+// --settlements <settlements.csv> writes every settlement's size, population and growth, and
+// which ones merged or split, for every epoch.
+// When the config has regions, <cols> and <rows> are ignored and every region runs, each printed
+// on its own row; --region <name> picks the one saved, journaled and measured, the first one by
+// default, and --migrations <migrations.csv> writes how many agents moved between regions.
+
+use nannou::prelude::*;
+
+use explorers_and_settlers::chronicle;
+use explorers_and_settlers::config::Config;
+use explorers_and_settlers::journal::Journal;
+use explorers_and_settlers::metrics::{self, Metrics};
+use explorers_and_settlers::region::Atlas;
+use explorers_and_settlers::settlement;
+use explorers_and_settlers::simulation::Stats;
+use explorers_and_settlers::snapshot::Snapshot;
+use explorers_and_settlers::topology::Topology;
+
+const USAGE: &str = "usage: headless <cols> <rows> <epochs> [seed] [topology] [options]
+       headless --from <snapshot> <epochs> [options]
+options: --config <path> --save <path> --journal <path> --metrics <path> --territories <path>
+         --traits <path> --graphml <path> --dot <path> --chronicles <path> --settlements <path>
+         --region <name> --migrations <path>";
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let from = take_flag(&mut args, "--from");
+    let config = match take_flag(&mut args, "--config") {
+        Some(path) => Config::load(&path).unwrap_or_else(|error| {
+            eprintln!("{}: {}", path, error);
+            std::process::exit(1);
+        }),
+        None => Config::default(),
+    };
+    let save = take_flag(&mut args, "--save");
+    let journal_path = take_flag(&mut args, "--journal");
+    let metrics_path = take_flag(&mut args, "--metrics");
+    let territories_path = take_flag(&mut args, "--territories");
+    let traits_path = take_flag(&mut args, "--traits");
+    let graphml_path = take_flag(&mut args, "--graphml");
+    let dot_path = take_flag(&mut args, "--dot");
+    let chronicles_path = take_flag(&mut args, "--chronicles");
+    let settlements_path = take_flag(&mut args, "--settlements");
+    let region = take_flag(&mut args, "--region");
+    let migrations_path = take_flag(&mut args, "--migrations");
+    let keep_metrics =
+        metrics_path.is_some() || territories_path.is_some() || traits_path.is_some();
+
+    let (mut atlas, epochs) = match from {
+        Some(path) => {
+            if args.len() != 1 {
+                exit_with_usage();
+            }
+
+            let world = Snapshot::load(&path)
+                .and_then(|snapshot| {
+                    snapshot.restore(config.grid.cell_size, config.grid.cell_spacing)
+                })
+                .unwrap_or_else(|error| {
+                    eprintln!("{}: {}", path, error);
+                    std::process::exit(1);
+                });
+
+            let atlas = Atlas::single("world", world, config.rules());
+            (atlas, parse_arg(&args[0], "epochs"))
+        }
+        None => {
+            if args.len() < 3 {
+                exit_with_usage();
+            }
+
+            let n_cols: usize = parse_arg(&args[0], "cols");
+            let n_rows: usize = parse_arg(&args[1], "rows");
+            let epochs: usize = parse_arg(&args[2], "epochs");
+            let seed: u64 = match args.get(3) {
+                Some(arg) => parse_arg(arg, "seed"),
+                None => random::<u64>(),
+            };
+            let topology: Topology = match args.get(4) {
+                Some(arg) => parse_arg(arg, "topology"),
+                None => Topology::default(),
+            };
+
+            // regions bring their own sizes
+            if config.regions.is_empty() {
+                if let Err(reason) = topology.fits(n_cols, n_rows) {
+                    eprintln!("{}\n{}", reason, USAGE);
+                    std::process::exit(1);
+                }
+            }
+
+            // cell size and spacing from the config only matter for drawing
+            let atlas = config
+                .build_atlas(n_cols, n_rows, topology, seed)
+                .unwrap_or_else(|error| {
+                    eprintln!("{}", error);
+                    std::process::exit(1);
+                });
+
+            (atlas, epochs)
+        }
+    };
+
+    let focus = match &region {
+        Some(name) => atlas.find(name).unwrap_or_else(|| {
+            eprintln!("no region named {}", name);
+            std::process::exit(1);
+        }),
+        None => 0,
+    };
+    let several = atlas.regions.len() > 1;
+
+    // a snapshot holds a single world, the other regions and their portals would be lost
+    if save.is_some() && several {
+        eprintln!(
+            "--save only works with a single region, this config has {}",
+            atlas.regions.len()
+        );
+        std::process::exit(1);
+    }
+    let mut journal = Journal::new();
+    let mut history = Vec::new();
+    let mut flows = Vec::new();
+    let mut settlements = config.settlements(atlas.regions[focus].world.seed);
+    let mut settlement_history = Vec::new();
+
+    for region in atlas.regions.iter() {
+        let world = &region.world;
+        println!(
+            "# seed {} grid {}x{} agents {} from epoch {}{}",
+            world.seed,
+            world.grid.n_cols,
+            world.grid.n_rows,
+            world.agents.len(),
+            world.epoch,
+            if several {
+                format!(" in {}", region.name)
+            } else {
+                String::new()
+            }
+        );
+    }
+    println!(
+        "epoch\tfilled\tblocked\tsettled\texploring{}",
+        if several { "\tregion" } else { "" }
+    );
+
+    for _ in 0..epochs {
+        let mut events = atlas.step();
+
+        if journal_path.is_some() {
+            let world = &atlas.regions[focus].world;
+            journal.record(world.epoch, events.swap_remove(focus));
+        }
+
+        for region in atlas.regions.iter() {
+            let world = &region.world;
+            let stats = Stats::new(world);
+            println!(
+                "{}\t{}\t{}\t{}\t{}{}",
+                world.epoch,
+                stats.filled,
+                stats.blocked,
+                stats.settled,
+                stats.exploring,
+                if several {
+                    format!("\t{}", region.name)
+                } else {
+                    String::new()
+                }
+            );
+        }
+
+        if keep_metrics {
+            history.push(Metrics::new(&atlas.regions[focus].world));
+        }
+
+        if migrations_path.is_some() {
+            flows.extend(atlas.flows());
+        }
+
+        if settlements_path.is_some() {
+            settlements.update(&atlas.regions[focus].world);
+            settlement_history.extend(settlements.iter().cloned());
+        }
+    }
+
+    let world = &atlas.regions[focus].world;
+
+    if let Some(path) = journal_path {
+        if let Err(error) = journal.save(&path) {
+            eprintln!("{}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = metrics_path {
+        if let Err(error) = metrics::save_csv(&path, &history) {
+            eprintln!("{}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = territories_path {
+        if let Err(error) = metrics::save_territory_csv(&path, &history) {
+            eprintln!("{}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = traits_path {
+        if let Err(error) = metrics::save_traits_csv(&path, &history) {
+            eprintln!("{}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = graphml_path {
+        if let Err(error) = world.social.save_graphml(&path, &world.agents) {
+            eprintln!("{}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = dot_path {
+        if let Err(error) = world.social.save_dot(&path, &world.agents) {
+            eprintln!("{}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = chronicles_path {
+        if let Err(error) = chronicle::save(&path, &world.agents) {
+            eprintln!("{}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = settlements_path {
+        if let Err(error) = settlement::save_csv(&path, &settlement_history) {
+            eprintln!("{}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = migrations_path {
+        if let Err(error) = metrics::save_migration_csv(&path, &flows) {
+            eprintln!("{}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = save {
+        if let Err(error) = Snapshot::capture(world).save(&path) {
+            eprintln!("{}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
+}
+
+// removes `--flag value` from the arguments and returns the value
+fn take_flag(args: &mut Vec<String>, flag: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == flag)?;
+
+    if index + 1 >= args.len() {
+        exit_with_usage();
+    }
+
+    args.remove(index);
+    Some(args.remove(index))
+}
+
+fn parse_arg<T: std::str::FromStr>(arg: &str, name: &str) -> T {
+    arg.parse().unwrap_or_else(|_| {
+        eprintln!("invalid {}: {}\n{}", name, arg, USAGE);
+        std::process::exit(1);
+    })
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(1);
+}
@@ -1,113 +1,136 @@
-use std::collections::HashMap;
-
 use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::AgentId;
+use crate::journal::Event;
+use crate::terrain::Terrain;
+use crate::topology::CellCoord;
 
-use crate::agent::Agent;
+// how many times an owner can fill a cell before it gets blocked, unless configured otherwise
+pub const MAX_TIMES: i32 = 6;
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum CellState {
     Empty,
+    Obstacle, // painted by hand, nobody can claim or cross it
     Filled {
-        by: String,
+        by: AgentId,
         times: i32,
         blocked: bool,
     },
 }
 
 pub struct Cell {
-    pub row: usize,
-    pub col: usize,
+    pub coord: CellCoord,
     pub rect: Rect,
     pub state: CellState,
+    pub terrain: Terrain,
+    pub blocked_for: u32, // epochs since the cell was blocked, while it is
 }
 
 impl Cell {
-    pub fn new(row: usize, col: usize, rect: Rect) -> Self {
+    pub fn new(coord: CellCoord, rect: Rect) -> Self {
         Cell {
-            row,
-            col,
+            coord,
             rect,
             state: CellState::Empty,
+            terrain: Terrain::default(),
+            blocked_for: 0,
         }
     }
 
-    fn get_neighbors(&self, cells_map: &HashMap<String, usize>) -> Vec<usize> {
-        let mut neighbors: Vec<usize> = Vec::new();
+    // returns what changed, if anything
+    pub fn fill(&mut self, id: AgentId, max_times: i32) -> Option<Event> {
+        let coord = self.coord;
 
-        let mut row = self.row;
-        let mut col = self.col;
+        match &mut self.state {
+            CellState::Obstacle => None,
+            CellState::Empty => {
+                self.state = CellState::Filled {
+                    by: id,
+                    times: 1,
+                    blocked: false,
+                };
 
-        // top
-        row -= 1;
-        if row >= 0 {
-            let key = format!("{}{}", row, col);
-            if let Some(index) = cells_map.get(&key) {
-                neighbors.push(*index);
+                Some(Event::CellFilled {
+                    coord,
+                    by: id,
+                    times: 1,
+                })
             }
-        }
+            CellState::Filled { by, times, blocked } => {
+                if *by != id || *blocked {
+                    None
+                } else if *times < max_times {
+                    *times += 1;
 
-        // bottom
-        row += 2;
-        if row < 100 {
-            let key = format!("{}{}", row, col);
-            if let Some(index) = cells_map.get(&key) {
-                neighbors.push(*index);
-            }
-        }
+                    Some(Event::CellFilled {
+                        coord,
+                        by: *by,
+                        times: *times,
+                    })
+                } else {
+                    *blocked = true;
+                    self.blocked_for = 0;
 
-        // left
-        row -= 1;
-        col -= 1;
-        if col >= 0 {
-            let key = format!("{}{}", row, col);
-            if let Some(index) = cells_map.get(&key) {
-                neighbors.push(*index);
+                    Some(Event::CellBlocked { coord, by: *by })
+                }
             }
         }
+    }
 
-        // right
-        col += 2;
-        if col < 100 {
-            let key = format!("{}{}", row, col);
-            if let Some(index) = cells_map.get(&key) {
-                neighbors.push(*index);
+    // one fill fewer, as the owner stays away; the cell is empty again at zero
+    pub fn fade(&mut self) -> Option<Event> {
+        let coord = self.coord;
+
+        let (by, times) = match &mut self.state {
+            CellState::Filled { by, times, .. } => {
+                *times -= 1;
+                (*by, *times)
             }
+            CellState::Empty | CellState::Obstacle => return None,
+        };
+
+        if times <= 0 {
+            self.state = CellState::Empty;
         }
 
-        neighbors
+        Some(Event::CellFaded { coord, by, times })
     }
 
-    pub fn fill(&mut self, agent: &Agent) {
-        self.state = match &self.state {
-            CellState::Empty => CellState::Filled {
-                by: agent.id.clone(),
-                times: 1,
-                blocked: false,
-            },
-            CellState::Filled { by, times, blocked } => {
-                let same_agent = by.eq(&agent.id);
-                if !blocked && same_agent && times.clone() < 6 {
-                    CellState::Filled {
-                        by: agent.id.clone(),
-                        times: times + 1,
-                        blocked: false,
-                    }
-                } else if same_agent {
-                    CellState::Filled {
-                        by: agent.id.clone(),
-                        times: times.clone(),
-                        blocked: true,
-                    }
-                } else {
-                    CellState::Filled {
-                        by: by.clone(),
-                        times: times.clone(),
-                        blocked: blocked.clone(),
-                    }
+    // a blocked cell takes fills again once it has been blocked for `unblock_after` epochs
+    pub fn cool_down(&mut self, unblock_after: u32) -> Option<Event> {
+        match &mut self.state {
+            CellState::Filled { by, blocked, .. } if *blocked => {
+                self.blocked_for += 1;
+
+                if self.blocked_for < unblock_after {
+                    return None;
                 }
+
+                *blocked = false;
+                self.blocked_for = 0;
+
+                Some(Event::CellUnblocked {
+                    coord: self.coord,
+                    by: *by,
+                })
             }
+            _ => None,
         }
     }
 
+    pub fn owner(&self) -> Option<AgentId> {
+        match self.state {
+            CellState::Filled { by, .. } => Some(by),
+            CellState::Empty | CellState::Obstacle => None,
+        }
+    }
+
+    pub fn is_passable(&self) -> bool {
+        self.state != CellState::Obstacle
+    }
+
     pub fn draw(&self, draw: &Draw, color: Hsv) {
         let x = self.rect.x();
         let y = self.rect.y();
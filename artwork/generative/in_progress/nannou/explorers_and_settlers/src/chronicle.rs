@@ -0,0 +1,410 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent::{Agent, AgentId, Direction};
+use crate::journal::Event;
+use crate::registry::AgentRegistry;
+use crate::rules::Transition;
+use crate::simulation::World;
+use crate::topology::CellCoord;
+
+// the turns an agent's life takes, as opposed to every step of it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Milestone {
+    Appeared {
+        at: CellCoord, // one of the first agents, placed by hand or from before chronicles
+        heading: Direction,
+    },
+    Born {
+        at: CellCoord,
+        parent: AgentId,
+        heading: Direction,
+    },
+    Settled {
+        at: CellCoord,
+        after: usize, // epochs spent exploring
+    },
+    LeftHome {
+        from: CellCoord,
+        after: usize, // epochs spent settled
+        heading: Direction,
+    },
+    HadChild {
+        child: AgentId,
+        at: CellCoord,
+    },
+    LostLand, // the last of its cells faded while it was away
+    Departed {
+        at: CellCoord, // the portal
+        to: String,
+    },
+    Arrived {
+        at: CellCoord,
+        from: String,
+        id: AgentId, // the agent's id from here on
+    },
+}
+
+impl Milestone {
+    // whether the agent settled or set off with this, for milestones that start a stage of life
+    fn settles(&self) -> Option<bool> {
+        match self {
+            Milestone::Appeared { heading, .. } | Milestone::Born { heading, .. } => {
+                Some(*heading == Direction::Settle)
+            }
+            Milestone::Settled { .. } => Some(true),
+            Milestone::LeftHome { .. } => Some(false),
+            _ => None,
+        }
+    }
+}
+
+// where an explorer is headed, in words
+fn heading(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Settle => "nowhere",
+        Direction::Left => "west",
+        Direction::Right => "east",
+        Direction::Up => "north",
+        Direction::Down => "south",
+    }
+}
+
+// `1 epoch`, `8 epochs`
+fn epochs(n: usize) -> String {
+    format!("{} epoch{}", n, if n == 1 { "" } else { "s" })
+}
+
+impl fmt::Display for Milestone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Milestone::Appeared {
+                at,
+                heading: Direction::Settle,
+            } => write!(f, "appeared at {} and settled there", at),
+            Milestone::Appeared { at, heading: to } => {
+                write!(f, "appeared at {} and set off {}", at, heading(*to))
+            }
+            Milestone::Born { at, parent, .. } => write!(f, "was born to {} at {}", parent, at),
+            Milestone::Settled { at, after } => {
+                write!(
+                    f,
+                    "settled at {} after exploring for {}",
+                    at,
+                    epochs(*after)
+                )
+            }
+            Milestone::LeftHome {
+                from,
+                after,
+                heading: to,
+            } => write!(
+                f,
+                "left home at {} after {}, heading {}",
+                from,
+                epochs(*after),
+                heading(*to)
+            ),
+            Milestone::HadChild { child, at } => write!(f, "had a child, {}, at {}", child, at),
+            Milestone::LostLand => write!(f, "lost the last of its land while it was away"),
+            Milestone::Departed { at, to } => {
+                write!(f, "left for {} through the portal at {}", to, at)
+            }
+            Milestone::Arrived { at, from, id } => {
+                write!(
+                    f,
+                    "arrived at {} from {}, known as {} from then on",
+                    at, from, id
+                )
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub epoch: usize,
+    pub milestone: Milestone,
+}
+
+impl fmt::Display for Chapter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "epoch {}: {}", self.epoch, self.milestone)
+    }
+}
+
+// an agent's life story, oldest chapter first
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Chronicle {
+    chapters: Vec<Chapter>,
+}
+
+impl Chronicle {
+    pub fn new() -> Self {
+        Chronicle::default()
+    }
+
+    pub fn push(&mut self, epoch: usize, milestone: Milestone) {
+        self.chapters.push(Chapter { epoch, milestone });
+    }
+
+    pub fn chapters(&self) -> &[Chapter] {
+        &self.chapters
+    }
+
+    pub fn len(&self) -> usize {
+        self.chapters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chapters.is_empty()
+    }
+
+    // whether the agent is settled according to its story, and since which epoch
+    fn stage(&self) -> Option<(bool, usize)> {
+        self.chapters.iter().rev().find_map(|chapter| {
+            let settled = chapter.milestone.settles()?;
+            Some((settled, chapter.epoch))
+        })
+    }
+}
+
+// add whatever `event` means to the lives of the agents it's about, once it has happened;
+// run for every event of an epoch, live or replayed, so both tell the same stories
+pub fn write(world: &mut World, epoch: usize, event: &Event) {
+    match *event {
+        Event::AgentRedecided { id, transition } => {
+            let agent = match world.agents.get_mut(id) {
+                Some(agent) => agent,
+                None => return,
+            };
+
+            let at = agent.position;
+            let heading = match transition {
+                Transition::Settle { .. } => Direction::Settle,
+                Transition::Explore { direction, .. } => direction,
+            };
+            let settles = heading == Direction::Settle;
+
+            // most re-decisions go on with the same stage of life
+            let milestone = match agent.chronicle.stage() {
+                None => Milestone::Appeared { at, heading },
+                Some((settled, _)) if settled == settles => return,
+                Some((_, since)) if settles => Milestone::Settled {
+                    at,
+                    after: epoch - since,
+                },
+                Some((_, since)) => Milestone::LeftHome {
+                    from: at,
+                    after: epoch - since,
+                    heading,
+                },
+            };
+
+            agent.chronicle.push(epoch, milestone);
+        }
+        Event::AgentBorn { parent, ref agent } => {
+            let (child, at) = (agent.id, agent.position);
+
+            if let Some(parent) = world.agents.get_mut(parent) {
+                parent
+                    .chronicle
+                    .push(epoch, Milestone::HadChild { child, at });
+            }
+
+            if let Some(child) = world.agents.get_mut(child) {
+                let heading = agent.direction();
+                child.chronicle.push(
+                    epoch,
+                    Milestone::Born {
+                        at,
+                        parent,
+                        heading,
+                    },
+                );
+            }
+        }
+        Event::CellFaded { by, times: 0, .. } => {
+            if world.agents.territory_size(by) > 0 {
+                return;
+            }
+
+            // several cells can fade away in the same epoch
+            match world.agents.get_mut(by) {
+                Some(agent)
+                    if agent
+                        .chronicle
+                        .chapters
+                        .last()
+                        .map(|chapter| &chapter.milestone)
+                        != Some(&Milestone::LostLand) =>
+                {
+                    agent.chronicle.push(epoch, Milestone::LostLand);
+                }
+                _ => (),
+            }
+        }
+        _ => (),
+    }
+}
+
+// the opening chapter of an agent that didn't come from anywhere in this run
+pub fn appear(agent: &mut Agent, epoch: usize) {
+    let milestone = Milestone::Appeared {
+        at: agent.position,
+        heading: agent.direction(),
+    };
+
+    agent.chronicle.push(epoch, milestone);
+}
+
+// every agent's story, one section each
+pub fn write_markdown(agents: &AgentRegistry, writer: &mut impl Write) -> io::Result<()> {
+    writeln!(writer, "# Chronicles")?;
+
+    for agent in agents.iter() {
+        writeln!(writer)?;
+        writeln!(writer, "## {}", agent.id)?;
+        writeln!(writer)?;
+
+        for chapter in agent.chronicle.chapters() {
+            writeln!(writer, "- {}", chapter)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Story<'a> {
+    agent: AgentId,
+    chapters: Vec<Told<'a>>,
+}
+
+// a chapter as data, along with the sentence it reads as
+#[derive(Serialize)]
+struct Told<'a> {
+    epoch: usize,
+    text: String,
+    milestone: &'a Milestone,
+}
+
+pub fn write_json(agents: &AgentRegistry, writer: &mut impl Write) -> io::Result<()> {
+    let stories: Vec<Story> = agents
+        .iter()
+        .map(|agent| Story {
+            agent: agent.id,
+            chapters: agent
+                .chronicle
+                .chapters()
+                .iter()
+                .map(|chapter| Told {
+                    epoch: chapter.epoch,
+                    text: chapter.milestone.to_string(),
+                    milestone: &chapter.milestone,
+                })
+                .collect(),
+        })
+        .collect();
+
+    serde_json::to_writer_pretty(&mut *writer, &stories)?;
+    writeln!(writer)
+}
+
+// json for a .json path, markdown otherwise
+pub fn save(path: impl AsRef<Path>, agents: &AgentRegistry) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        write_json(agents, &mut writer)?;
+    } else {
+        write_markdown(agents, &mut writer)?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+    use crate::rules::{Births, RuleRegistry};
+    use crate::timeline::Timeline;
+    use crate::topology::Topology;
+
+    #[test]
+    fn stories_are_told_the_same_live_and_replayed() {
+        let mut rules = RuleRegistry::with_life_events();
+        rules.births = Births {
+            chance: 0.05,
+            mutation: 0.1,
+        };
+        let grid = Grid::new(40, 30, 16.0, 2.0, Topology::default());
+        let mut world = World::new(grid, 3);
+        let mut timeline = Timeline::new(&world);
+
+        for _ in 0..80 {
+            let events = world.step(&rules);
+            timeline.record(world.epoch, events);
+        }
+
+        let chapters: Vec<&Chapter> = world
+            .agents
+            .iter()
+            .flat_map(|agent| agent.chronicle.chapters())
+            .collect();
+        let count = |found: fn(&Milestone) -> bool| {
+            chapters
+                .iter()
+                .filter(|chapter| found(&chapter.milestone))
+                .count()
+        };
+
+        assert!(
+            count(|milestone| matches!(milestone, Milestone::Settled { after, .. } if *after > 0))
+                > 0
+        );
+        assert!(
+            count(|milestone| matches!(milestone, Milestone::LeftHome { after, .. } if *after > 0))
+                > 0
+        );
+        assert!(count(|milestone| matches!(milestone, Milestone::Born { .. })) > 0);
+        assert_eq!(
+            count(|milestone| matches!(milestone, Milestone::Born { .. })),
+            count(|milestone| matches!(milestone, Milestone::HadChild { .. }))
+        );
+
+        // every story starts with the agent appearing or being born
+        for agent in world.agents.iter() {
+            let first = &agent.chronicle.chapters()[0].milestone;
+            assert!(matches!(
+                first,
+                Milestone::Appeared { .. } | Milestone::Born { .. }
+            ));
+        }
+
+        let replayed = timeline.replay(world.epoch, 16.0, 2.0).unwrap();
+        let stories = |world: &World| -> Vec<Chronicle> {
+            world
+                .agents
+                .iter()
+                .map(|agent| agent.chronicle.clone())
+                .collect()
+        };
+        assert_eq!(stories(&replayed), stories(&world));
+
+        let mut markdown = Vec::new();
+        write_markdown(&world.agents, &mut markdown).unwrap();
+        let markdown = String::from_utf8(markdown).unwrap();
+        assert!(markdown.contains("- epoch 0: appeared at "));
+    }
+}
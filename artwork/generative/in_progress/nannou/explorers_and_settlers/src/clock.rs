@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+// epochs a frame may run to catch up after a slow one, the rest of the backlog is dropped
+const MAX_CATCH_UP: usize = 4;
+
+// how fast the simulation runs, in epochs per second of real time rather than per frame, so it
+// runs the same on any monitor and however long drawing takes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clock {
+    pub epochs_per_second: f64,
+    pub fast_forward_epochs: usize, // run every frame while fast-forwarding
+    pub paused: bool,
+    pub fast_forward: bool,
+    due: f64,     // epochs owed, carried over between frames
+    steps: usize, // single epochs asked for while paused
+}
+
+impl Clock {
+    pub fn new(epochs_per_second: f64, fast_forward_epochs: usize) -> Self {
+        Clock {
+            epochs_per_second,
+            fast_forward_epochs,
+            paused: false,
+            fast_forward: false,
+            due: 0.0,
+            steps: 0,
+        }
+    }
+
+    // how many epochs to run this frame, `elapsed` after the last one
+    pub fn tick(&mut self, elapsed: Duration) -> usize {
+        if self.paused {
+            return std::mem::take(&mut self.steps);
+        }
+
+        if self.fast_forward {
+            self.due = 0.0;
+            return self.fast_forward_epochs;
+        }
+
+        self.due += elapsed.as_secs_f64() * self.epochs_per_second;
+
+        // the small margin keeps e.g. sixty frames of a tenth of an epoch at six epochs
+        let epochs = (self.due + 1e-9).floor() as usize;
+        self.due = (self.due - epochs as f64).max(0.0);
+
+        epochs.min(MAX_CATCH_UP)
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.steps = 0;
+        self.due = 0.0;
+    }
+
+    pub fn toggle_fast_forward(&mut self) {
+        self.fast_forward = !self.fast_forward;
+    }
+
+    // one epoch on the next frame, pausing first if the clock is running
+    pub fn step(&mut self) {
+        if !self.paused {
+            self.toggle_pause();
+        }
+
+        self.steps += 1;
+    }
+
+    // twice or half as fast, within reason
+    pub fn faster(&mut self) {
+        self.epochs_per_second = (self.epochs_per_second * 2.0).min(240.0);
+    }
+
+    pub fn slower(&mut self) {
+        self.epochs_per_second = (self.epochs_per_second / 2.0).max(0.25);
+    }
+
+    // e.g. `6 epochs/s`, `paused`, `fast-forward x20`
+    pub fn status(&self) -> String {
+        if self.paused {
+            "paused".to_string()
+        } else if self.fast_forward {
+            format!("fast-forward x{}", self.fast_forward_epochs)
+        } else {
+            format!("{} epochs/s", self.epochs_per_second)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epochs_follow_time_not_frames() {
+        let mut clock = Clock::new(6.0, 20);
+
+        // a second at 50 and at 125 frames per second both run 6 epochs
+        for fps in [50, 125] {
+            let frame = Duration::from_secs_f64(1.0 / fps as f64);
+            let epochs: usize = (0..fps).map(|_| clock.tick(frame)).sum();
+            assert_eq!(epochs, 6);
+        }
+
+        // a long stall only catches up a little
+        assert_eq!(clock.tick(Duration::from_secs(10)), MAX_CATCH_UP);
+
+        clock.step();
+        assert!(clock.paused);
+        assert_eq!(clock.tick(Duration::from_secs(1)), 1);
+        assert_eq!(clock.tick(Duration::from_secs(1)), 0);
+
+        clock.toggle_pause();
+        clock.toggle_fast_forward();
+        assert_eq!(clock.tick(Duration::from_millis(16)), 20);
+    }
+}
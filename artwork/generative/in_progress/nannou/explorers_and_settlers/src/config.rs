@@ -0,0 +1,663 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use nannou::image::ImageError;
+use serde::{Deserialize, Serialize};
+
+use crate::cell::MAX_TIMES;
+use crate::clock::Clock;
+use crate::grid::Grid;
+use crate::influence::{self, InfluenceField};
+use crate::region::{Atlas, Portal, Region};
+use crate::rules::{Births, Decay, Destinations, RuleRegistry};
+use crate::settlement::Settlements;
+use crate::simulation::{Schedule, World, AGENT_DENSITY};
+use crate::terrain;
+use crate::topology::{CellCoord, Topology};
+
+// what `frames_per_epoch` counted against before the sketch had a clock
+const OLD_FRAME_RATE: f64 = 60.0;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Invalid(String),
+    Terrain(String, ImageError), // the map's path and what went wrong reading it
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "could not read config: {}", error),
+            ConfigError::Parse(error) => write!(f, "could not parse config: {}", error),
+            ConfigError::Invalid(reason) => write!(f, "invalid config: {}", reason),
+            ConfigError::Terrain(path, error) => {
+                write!(f, "could not read terrain map {}: {}", path, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(error: io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(error: toml::de::Error) -> Self {
+        ConfigError::Parse(error)
+    }
+}
+
+// the parameters an artist tunes a piece with; anything left out keeps its default
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub grid: GridConfig,
+    pub palettes: PalettesConfig,
+    pub simulation: SimulationConfig,
+    pub regions: Vec<RegionConfig>, // none means a single region filling the window
+    pub portals: Vec<PortalConfig>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GridConfig {
+    pub cell_size: f32,    // initial size of each square, it scales with the window
+    pub cell_spacing: f32, // initial space between each square
+    pub terrain: Option<String>, // image stretched over the grid, see terrain::load_map
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        GridConfig {
+            cell_size: 16.0,
+            cell_spacing: 2.0,
+            terrain: None,
+        }
+    }
+}
+
+// a gradient from the first to the second value of each range
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PaletteConfig {
+    pub hue: [f32; 2],        // degrees
+    pub saturation: [f32; 2], // 0 to 1
+    pub value: [f32; 2],      // 0 to 1
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PalettesConfig {
+    pub colors: i32, // per palette, before it is mirrored
+    pub warm: PaletteConfig,
+    pub cool: PaletteConfig,
+    pub muted_warm: PaletteConfig,
+    pub muted_cool: PaletteConfig,
+}
+
+impl Default for PalettesConfig {
+    fn default() -> Self {
+        PalettesConfig {
+            colors: 360,
+            warm: PaletteConfig {
+                hue: [1.0, 180.0],
+                saturation: [0.6, 0.9],
+                value: [0.6, 0.8],
+            },
+            cool: PaletteConfig {
+                hue: [181.0, 360.0],
+                saturation: [0.6, 0.9],
+                value: [0.6, 0.8],
+            },
+            muted_warm: PaletteConfig {
+                hue: [1.0, 180.0],
+                saturation: [0.0, 0.1],
+                value: [0.6, 0.8],
+            },
+            muted_cool: PaletteConfig {
+                hue: [181.0, 360.0],
+                saturation: [0.0, 0.1],
+                value: [0.6, 0.8],
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimulationConfig {
+    pub agent_density: f64,         // agents per cell
+    pub epochs_per_second: f64,     // only used by the sketch, like the next one
+    pub fast_forward_epochs: usize, // run every frame while fast-forwarding
+    pub max_times: i32,             // fills before a cell is blocked
+    pub birth_chance: f32,          // for a settler to have a child each time it re-decides
+    pub mutation: f32,              // how far a child's traits can stray from its parent's
+    pub influence_diffusion: f32,   // how much influence spreads to neighboring cells each epoch
+    pub influence_decay: f32,       // the share of influence lost each epoch
+    pub abandon_after: u32, // epochs an owner is away before its cells start to fade, 0 for never
+    pub unblock_after: u32, // epochs a blocked cell stays blocked, 0 for ever
+    pub destination_chance: f32, // for an explorer to head for a cell instead of a direction
+    pub destination_radius: usize, // the farthest that cell can be, in steps
+    pub schedule: Schedule, // how agents take their turns, see `Schedule`
+    pub name_settlements: bool, // generated names for settlements instead of their ids
+    // the old frame based speed, read into `epochs_per_second` at 60 fps so older files still load
+    #[serde(skip_serializing)]
+    pub frames_per_epoch: Option<u64>,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            agent_density: AGENT_DENSITY,
+            epochs_per_second: 6.0,
+            fast_forward_epochs: 20,
+            max_times: MAX_TIMES,
+            birth_chance: 0.05,
+            mutation: 0.1,
+            influence_diffusion: influence::DIFFUSION,
+            influence_decay: influence::DECAY,
+            abandon_after: 20,
+            unblock_after: 30,
+            destination_chance: 0.3,
+            destination_radius: 12,
+            schedule: Schedule::default(),
+            name_settlements: true,
+            frames_per_epoch: None,
+        }
+    }
+}
+
+// a region of its own size, anything left out is taken from [grid] and [simulation]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegionConfig {
+    pub name: String,
+    pub cols: usize,
+    pub rows: usize,
+    pub terrain: Option<String>,
+    pub agent_density: Option<f64>,
+    pub max_times: Option<i32>,
+    pub birth_chance: Option<f32>,
+    pub mutation: Option<f32>,
+    pub rules: Option<Vec<String>>, // the life events that happen here, all of them by default
+}
+
+// a one way passage, add another one to come back
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PortalConfig {
+    pub from: String,   // region names
+    pub at: [usize; 2], // row and column
+    pub to: String,
+    pub exit: [usize; 2],
+    #[serde(default)]
+    pub delay: usize, // epochs on the way
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Config::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let mut config: Config = toml::from_str(text)?;
+
+        if let Some(frames) = config.simulation.frames_per_epoch.take() {
+            check(
+                frames >= 1,
+                "simulation.frames_per_epoch",
+                frames,
+                "must be at least 1, better use epochs_per_second instead",
+            )?;
+            config.simulation.epochs_per_second = OLD_FRAME_RATE / frames as f64;
+        }
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    // an empty grid with the configured fill cap and terrain
+    pub fn build_grid(
+        &self,
+        n_cols: usize,
+        n_rows: usize,
+        topology: Topology,
+    ) -> Result<Grid, ConfigError> {
+        self.grid_with(
+            n_cols,
+            n_rows,
+            topology,
+            self.simulation.max_times,
+            self.grid.terrain.as_ref(),
+        )
+    }
+
+    fn grid_with(
+        &self,
+        n_cols: usize,
+        n_rows: usize,
+        topology: Topology,
+        max_times: i32,
+        terrain: Option<&String>,
+    ) -> Result<Grid, ConfigError> {
+        topology
+            .fits(n_cols, n_rows)
+            .map_err(ConfigError::Invalid)?;
+
+        let mut grid = Grid::new(
+            n_cols,
+            n_rows,
+            self.grid.cell_size,
+            self.grid.cell_spacing,
+            topology,
+        );
+        grid.max_times = max_times;
+        grid.influence = InfluenceField::new(
+            self.simulation.influence_diffusion,
+            self.simulation.influence_decay,
+        );
+
+        if let Some(path) = terrain {
+            terrain::load_map(path, &mut grid)
+                .map_err(|error| ConfigError::Terrain(path.clone(), error))?;
+        }
+
+        Ok(grid)
+    }
+
+    // every configured region populated from the seed, each one with a seed of its own;
+    // without regions, a single one of `n_cols` by `n_rows` cells
+    pub fn build_atlas(
+        &self,
+        n_cols: usize,
+        n_rows: usize,
+        topology: Topology,
+        seed: u64,
+    ) -> Result<Atlas, ConfigError> {
+        if self.regions.is_empty() {
+            let grid = self.build_grid(n_cols, n_rows, topology)?;
+            let mut world = World::with_density(grid, seed, self.simulation.agent_density);
+            world.schedule = self.simulation.schedule;
+
+            return Ok(Atlas::single("world", world, self.rules()));
+        }
+
+        let mut regions = Vec::new();
+
+        for (index, region) in self.regions.iter().enumerate() {
+            let grid = self.grid_with(
+                region.cols,
+                region.rows,
+                topology,
+                region.max_times.unwrap_or(self.simulation.max_times),
+                region.terrain.as_ref().or(self.grid.terrain.as_ref()),
+            )?;
+            let density = region
+                .agent_density
+                .unwrap_or(self.simulation.agent_density);
+
+            let mut world = World::with_density(grid, seed.wrapping_add(index as u64), density);
+            world.schedule = self.simulation.schedule;
+
+            regions.push(Region {
+                name: region.name.clone(),
+                world,
+                rules: self.region_rules(region),
+            });
+        }
+
+        let mut atlas = Atlas::new(regions);
+
+        // names were checked by `validate`
+        for portal in self.portals.iter() {
+            let coord = |[row, col]: [usize; 2]| CellCoord::new(row, col);
+
+            if let (Some(from), Some(to)) = (atlas.find(&portal.from), atlas.find(&portal.to)) {
+                atlas.portals.push(Portal {
+                    from,
+                    at: coord(portal.at),
+                    to,
+                    exit: coord(portal.exit),
+                    delay: portal.delay,
+                });
+            }
+        }
+
+        Ok(atlas)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let grid = &self.grid;
+        let simulation = &self.simulation;
+
+        check(
+            grid.cell_size > 0.0,
+            "grid.cell_size",
+            grid.cell_size,
+            "must be positive",
+        )?;
+        check(
+            grid.cell_spacing >= 0.0,
+            "grid.cell_spacing",
+            grid.cell_spacing,
+            "can't be negative",
+        )?;
+
+        let palettes = &self.palettes;
+        check(
+            palettes.colors >= 2,
+            "palettes.colors",
+            palettes.colors,
+            "must be at least 2",
+        )?;
+
+        for (name, palette) in [
+            ("warm", &palettes.warm),
+            ("cool", &palettes.cool),
+            ("muted_warm", &palettes.muted_warm),
+            ("muted_cool", &palettes.muted_cool),
+        ] {
+            for (range, values, max) in [
+                ("hue", palette.hue, 360.0),
+                ("saturation", palette.saturation, 1.0),
+                ("value", palette.value, 1.0),
+            ] {
+                check(
+                    values.iter().all(|v| (0.0..=max).contains(v)),
+                    &format!("palettes.{}.{}", name, range),
+                    format!("{:?}", values),
+                    &format!("must be between 0 and {}", max),
+                )?;
+            }
+        }
+
+        check(
+            simulation.agent_density > 0.0 && simulation.agent_density <= 1.0,
+            "simulation.agent_density",
+            simulation.agent_density,
+            "must be above 0 and at most 1",
+        )?;
+        check(
+            simulation.epochs_per_second > 0.0,
+            "simulation.epochs_per_second",
+            simulation.epochs_per_second,
+            "must be above 0",
+        )?;
+        check(
+            simulation.fast_forward_epochs >= 1,
+            "simulation.fast_forward_epochs",
+            simulation.fast_forward_epochs,
+            "must be at least 1",
+        )?;
+        check(
+            simulation.max_times >= 1,
+            "simulation.max_times",
+            simulation.max_times,
+            "must be at least 1",
+        )?;
+        check(
+            (0.0..=1.0).contains(&simulation.birth_chance),
+            "simulation.birth_chance",
+            simulation.birth_chance,
+            "must be between 0 and 1",
+        )?;
+        check(
+            (0.0..=1.0).contains(&simulation.mutation),
+            "simulation.mutation",
+            simulation.mutation,
+            "must be between 0 and 1",
+        )?;
+        check(
+            (0.0..=1.0).contains(&simulation.influence_diffusion),
+            "simulation.influence_diffusion",
+            simulation.influence_diffusion,
+            "must be between 0 and 1",
+        )?;
+        check(
+            simulation.influence_decay > 0.0 && simulation.influence_decay <= 1.0,
+            "simulation.influence_decay",
+            simulation.influence_decay,
+            "must be above 0 and at most 1",
+        )?;
+        check(
+            (0.0..=1.0).contains(&simulation.destination_chance),
+            "simulation.destination_chance",
+            simulation.destination_chance,
+            "must be between 0 and 1",
+        )?;
+
+        self.validate_regions()
+    }
+
+    fn validate_regions(&self) -> Result<(), ConfigError> {
+        let rule_names = RuleRegistry::with_life_events().names();
+
+        for (index, region) in self.regions.iter().enumerate() {
+            let key = |field: &str| format!("regions.{}.{}", region.name, field);
+
+            check(
+                !region.name.is_empty(),
+                &format!("regions[{}].name", index),
+                "\"\"",
+                "can't be empty",
+            )?;
+            check(
+                self.regions[..index]
+                    .iter()
+                    .all(|other| other.name != region.name),
+                &key("name"),
+                &region.name,
+                "must be unique",
+            )?;
+            check(
+                region.cols >= 1 && region.rows >= 1,
+                &key("cols and rows"),
+                format!("{}x{}", region.cols, region.rows),
+                "must be at least 1",
+            )?;
+
+            if let Some(density) = region.agent_density {
+                check(
+                    density > 0.0 && density <= 1.0,
+                    &key("agent_density"),
+                    density,
+                    "must be above 0 and at most 1",
+                )?;
+            }
+
+            if let Some(max_times) = region.max_times {
+                check(
+                    max_times >= 1,
+                    &key("max_times"),
+                    max_times,
+                    "must be at least 1",
+                )?;
+            }
+
+            for (field, value) in [
+                ("birth_chance", region.birth_chance),
+                ("mutation", region.mutation),
+            ] {
+                if let Some(value) = value {
+                    check(
+                        (0.0..=1.0).contains(&value),
+                        &key(field),
+                        value,
+                        "must be between 0 and 1",
+                    )?;
+                }
+            }
+
+            for rule in region.rules.iter().flatten() {
+                check(
+                    rule_names.contains(&rule.as_str()),
+                    &key("rules"),
+                    rule,
+                    &format!("must be one of {}", rule_names.join(", ")),
+                )?;
+            }
+        }
+
+        for (index, portal) in self.portals.iter().enumerate() {
+            let key = |field: &str| format!("portals[{}].{}", index, field);
+
+            for (region_field, name, cell_field, [row, col]) in [
+                ("from", &portal.from, "at", portal.at),
+                ("to", &portal.to, "exit", portal.exit),
+            ] {
+                let region = self.regions.iter().find(|region| &region.name == name);
+                check(
+                    region.is_some(),
+                    &key(region_field),
+                    name,
+                    "must name a region",
+                )?;
+
+                if let Some(region) = region {
+                    check(
+                        row < region.rows && col < region.cols,
+                        &key(cell_field),
+                        format!("[{}, {}]", row, col),
+                        &format!("must be inside {}", region.name),
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // the built-in life events, with births and decay tuned by the config
+    pub fn rules(&self) -> RuleRegistry {
+        let mut rules = RuleRegistry::with_life_events();
+        rules.births = Births {
+            chance: self.simulation.birth_chance,
+            mutation: self.simulation.mutation,
+        };
+        rules.decay = Decay {
+            abandon_after: self.simulation.abandon_after,
+            unblock_after: self.simulation.unblock_after,
+        };
+        rules.destinations = Destinations {
+            chance: self.simulation.destination_chance,
+            radius: self.simulation.destination_radius,
+        };
+
+        rules
+    }
+
+    // the sketch's simulation clock, running
+    pub fn clock(&self) -> Clock {
+        Clock::new(
+            self.simulation.epochs_per_second,
+            self.simulation.fast_forward_epochs,
+        )
+    }
+
+    // a tracker for the settlements of a world run from `seed`, named if the config says so
+    pub fn settlements(&self, seed: u64) -> Settlements {
+        if self.simulation.name_settlements {
+            Settlements::with_names(seed)
+        } else {
+            Settlements::new()
+        }
+    }
+
+    // the life events a region allows, with its own births
+    fn region_rules(&self, region: &RegionConfig) -> RuleRegistry {
+        let mut rules = self.rules();
+        rules.births = Births {
+            chance: region.birth_chance.unwrap_or(rules.births.chance),
+            mutation: region.mutation.unwrap_or(rules.births.mutation),
+        };
+
+        if let Some(allowed) = &region.rules {
+            for name in rules.names() {
+                rules.set_enabled(name, allowed.iter().any(|rule| rule == name));
+            }
+        }
+
+        rules
+    }
+}
+
+fn check(ok: bool, key: &str, value: impl fmt::Display, rule: &str) -> Result<(), ConfigError> {
+    if ok {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid(format!(
+            "{} {}, got {}",
+            key, rule, value
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_files_keep_defaults_and_bad_values_are_named() {
+        let config = Config::parse("[simulation]\nmax_times = 3\n").unwrap();
+        assert_eq!(config.simulation.max_times, 3);
+        assert_eq!(config.grid, GridConfig::default());
+
+        let error = Config::parse(
+            "[palettes.warm]\nhue = [0.0, 400.0]\nsaturation = [0.5, 0.5]\nvalue = [0.5, 0.5]\n",
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("palettes.warm.hue"), "{}", error);
+
+        assert!(matches!(
+            Config::parse("[grid]\ncell_sise = 4.0\n"),
+            Err(ConfigError::Parse(_))
+        ));
+
+        let old = Config::parse("[simulation]\nframes_per_epoch = 10\n").unwrap();
+        assert_eq!(old.simulation.epochs_per_second, 6.0);
+        assert_eq!(old.simulation.frames_per_epoch, None);
+    }
+
+    #[test]
+    fn grids_must_suit_their_topology() {
+        let config = Config::default();
+        let hex_torus: Topology = "hexagonal-torus".parse().unwrap();
+
+        assert!(config.build_grid(10, 8, hex_torus).is_ok());
+        assert!(config.build_grid(10, 7, hex_torus).is_err());
+        assert!(config.build_grid(10, 7, Topology::default()).is_ok());
+        assert!(config.build_grid(0, 7, Topology::default()).is_err());
+    }
+
+    #[test]
+    fn portals_must_lead_into_named_regions() {
+        let regions = "[[regions]]\nname = \"city\"\ncols = 10\nrows = 8\n\
+                       [[regions]]\nname = \"country\"\ncols = 20\nrows = 8\n";
+
+        let config = Config::parse(&format!(
+            "{}[[portals]]\nfrom = \"city\"\nat = [4, 9]\nto = \"country\"\nexit = [4, 0]\n",
+            regions
+        ))
+        .unwrap();
+        let atlas = config.build_atlas(0, 0, Topology::default(), 1).unwrap();
+        assert_eq!(atlas.regions[1].world.grid.n_cols, 20);
+        assert_eq!(atlas.portals[0].to, 1);
+
+        let error = Config::parse(&format!(
+            "{}[[portals]]\nfrom = \"city\"\nat = [4, 10]\nto = \"town\"\nexit = [4, 0]\n",
+            regions
+        ))
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("portals[0].at"), "{}", error);
+    }
+}
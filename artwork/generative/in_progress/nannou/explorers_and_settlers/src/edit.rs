@@ -0,0 +1,314 @@
+use std::fmt;
+
+use crate::agent::{AgentId, Direction};
+use crate::cell::CellState;
+use crate::chronicle;
+use crate::rules::Transition;
+use crate::simulation::World;
+use crate::snapshot::{Snapshot, SnapshotError};
+use crate::topology::CellCoord;
+
+// epochs a hand placed agent keeps its initial decision
+pub const PLACED_INTENSITY: i32 = 5;
+
+// how many strokes can be undone
+const UNDO_LIMIT: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tool {
+    Place(Direction), // a new agent, settled or heading somewhere
+    Erase,            // agents and everything they own
+    Obstacle,
+    Territory, // cells claimed for the selected agent
+    Clear,     // everything inside a dragged rectangle
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tool::Place(Direction::Settle) => write!(f, "place settled agent"),
+            Tool::Place(direction) => write!(f, "place agent heading {:?}", direction),
+            Tool::Erase => write!(f, "erase agents"),
+            Tool::Obstacle => write!(f, "paint obstacles"),
+            Tool::Territory => write!(f, "paint territory"),
+            Tool::Clear => write!(f, "clear region"),
+        }
+    }
+}
+
+// put a new agent on a cell, unless it's an obstacle
+pub fn place(world: &mut World, coord: CellCoord, direction: Direction) -> Option<AgentId> {
+    if !world.grid.cell(coord).is_passable() {
+        return None;
+    }
+
+    let transition = match direction {
+        Direction::Settle => Transition::Settle {
+            intensity: PLACED_INTENSITY,
+        },
+        direction => Transition::Explore {
+            direction,
+            intensity: PLACED_INTENSITY,
+        },
+    };
+
+    let id = world.agents.spawn_with(&mut world.grid, coord, transition);
+
+    if let Some(agent) = world.agents.get_mut(id) {
+        chronicle::appear(agent, world.epoch);
+    }
+
+    Some(id)
+}
+
+// remove the agents standing on a cell, along with their territory
+pub fn erase(world: &mut World, coord: CellCoord) -> bool {
+    let ids: Vec<AgentId> = world.agents.at(coord).map(|agent| agent.id).collect();
+
+    for &id in ids.iter() {
+        world.agents.remove(&mut world.grid, id);
+        world.social.forget(id);
+    }
+
+    !ids.is_empty()
+}
+
+pub fn paint_obstacle(world: &mut World, coord: CellCoord) -> bool {
+    if !world.grid.cell(coord).is_passable() {
+        return false;
+    }
+
+    erase(world, coord);
+    world.agents.release(coord);
+    world.grid.cell_mut(coord).state = CellState::Obstacle;
+
+    true
+}
+
+// hand a cell to an agent, as if it had just settled there
+pub fn claim(world: &mut World, coord: CellCoord, id: AgentId) -> bool {
+    let cell = world.grid.cell(coord);
+
+    if !cell.is_passable() || cell.owner() == Some(id) || world.agents.get(id).is_none() {
+        return false;
+    }
+
+    world.grid.cell_mut(coord).state = CellState::Filled {
+        by: id,
+        times: 1,
+        blocked: false,
+    };
+    world.agents.claim(id, coord);
+
+    true
+}
+
+// empty every cell in the rectangle between two corners and remove the agents on them
+pub fn clear(world: &mut World, from: CellCoord, to: CellCoord) -> bool {
+    let mut changed = false;
+
+    for row in from.row.min(to.row)..=from.row.max(to.row) {
+        for col in from.col.min(to.col)..=from.col.max(to.col) {
+            let coord = CellCoord::new(row, col);
+            changed |= erase(world, coord);
+
+            if world.grid.cell(coord).state != CellState::Empty {
+                world.agents.release(coord);
+                world.grid.cell_mut(coord).state = CellState::Empty;
+                changed = true;
+            }
+        }
+    }
+
+    changed
+}
+
+// turns mouse strokes into edits, keeping the world as it was before each stroke
+#[derive(Default)]
+pub struct Editor {
+    pub tool: Option<Tool>,
+    pub selected: Option<AgentId>, // whose territory gets painted
+    stroke: Option<Stroke>,
+    undo: Vec<Snapshot>,
+}
+
+struct Stroke {
+    start: CellCoord,
+    last: CellCoord,
+    changed: bool,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Editor::default()
+    }
+
+    // all of these return whether the world changed
+    pub fn press(&mut self, world: &mut World, coord: CellCoord) -> bool {
+        let tool = match self.tool {
+            Some(tool) => tool,
+            None => return false,
+        };
+
+        self.undo.push(Snapshot::capture(world));
+
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+
+        // pressing on an agent picks whose territory to paint
+        if tool == Tool::Territory {
+            if let Some(agent) = world.agents.at(coord).next() {
+                self.selected = Some(agent.id);
+            }
+        }
+
+        let changed = self.apply(world, tool, coord);
+
+        self.stroke = Some(Stroke {
+            start: coord,
+            last: coord,
+            changed,
+        });
+
+        changed
+    }
+
+    pub fn drag(&mut self, world: &mut World, coord: CellCoord) -> bool {
+        let tool = match (self.tool, &self.stroke) {
+            (Some(tool), Some(stroke)) if stroke.last != coord => tool,
+            _ => return false,
+        };
+
+        // agents are placed one per click, and regions are cleared on release
+        if matches!(tool, Tool::Place(_) | Tool::Clear) {
+            return false;
+        }
+
+        let changed = self.apply(world, tool, coord);
+
+        if let Some(stroke) = &mut self.stroke {
+            stroke.last = coord;
+            stroke.changed |= changed;
+        }
+
+        changed
+    }
+
+    // `coord` is None when the mouse was released outside the grid
+    pub fn release(&mut self, world: &mut World, coord: Option<CellCoord>) -> bool {
+        let mut stroke = match self.stroke.take() {
+            Some(stroke) => stroke,
+            None => return false,
+        };
+
+        let mut changed = false;
+
+        if self.tool == Some(Tool::Clear) {
+            changed = clear(world, stroke.start, coord.unwrap_or(stroke.last));
+            stroke.changed |= changed;
+        }
+
+        // nothing to undo
+        if !stroke.changed {
+            self.undo.pop();
+        }
+
+        changed
+    }
+
+    // forget strokes and the agent picked, e.g. when editing another world; the tool stays
+    pub fn reset(&mut self) {
+        self.selected = None;
+        self.stroke = None;
+        self.undo.clear();
+    }
+
+    // the cell a clear would start from, while the mouse is down
+    pub fn region_start(&self) -> Option<CellCoord> {
+        match (self.tool, &self.stroke) {
+            (Some(Tool::Clear), Some(stroke)) => Some(stroke.start),
+            _ => None,
+        }
+    }
+
+    // bring back the world from before the last stroke, keeping its cell size
+    pub fn undo(&mut self, world: &mut World) -> Result<bool, SnapshotError> {
+        let snapshot = match self.undo.pop() {
+            Some(snapshot) => snapshot,
+            None => return Ok(false),
+        };
+
+        *world = snapshot.restore(world.grid.cell_size, world.grid.cell_spacing)?;
+        self.stroke = None;
+
+        Ok(true)
+    }
+
+    fn apply(&mut self, world: &mut World, tool: Tool, coord: CellCoord) -> bool {
+        match tool {
+            Tool::Place(direction) => match place(world, coord, direction) {
+                Some(id) => {
+                    self.selected = Some(id);
+                    true
+                }
+                None => false,
+            },
+            Tool::Erase => erase(world, coord),
+            Tool::Obstacle => paint_obstacle(world, coord),
+            Tool::Territory => match self.selected {
+                Some(id) => claim(world, coord, id),
+                None => false,
+            },
+            Tool::Clear => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+    use crate::rules::RuleRegistry;
+    use crate::topology::Topology;
+
+    fn empty_world() -> World {
+        let grid = Grid::new(10, 10, 16.0, 2.0, Topology::default());
+        World::with_density(grid, 0, 0.0)
+    }
+
+    #[test]
+    fn obstacles_stop_explorers_and_strokes_undo() {
+        let mut world = empty_world();
+        let mut editor = Editor::new();
+
+        editor.tool = Some(Tool::Obstacle);
+        editor.press(&mut world, CellCoord::new(2, 4));
+        editor.drag(&mut world, CellCoord::new(2, 5));
+        editor.release(&mut world, Some(CellCoord::new(2, 5)));
+
+        editor.tool = Some(Tool::Place(Direction::Right));
+        editor.press(&mut world, CellCoord::new(2, 0));
+        editor.release(&mut world, Some(CellCoord::new(2, 0)));
+
+        let id = editor.selected.unwrap();
+        let rules = RuleRegistry::new();
+
+        for _ in 0..PLACED_INTENSITY - 1 {
+            world.step(&rules);
+        }
+
+        assert_eq!(world.agents.get(id).unwrap().position, CellCoord::new(2, 3));
+
+        assert!(editor.undo(&mut world).unwrap());
+        assert!(world.agents.is_empty());
+        assert_eq!(
+            world.grid.cell(CellCoord::new(2, 5)).state,
+            CellState::Obstacle
+        );
+
+        assert!(editor.undo(&mut world).unwrap());
+        assert!(world.grid.cell(CellCoord::new(2, 5)).is_passable());
+        assert!(!editor.undo(&mut world).unwrap());
+    }
+}
@@ -1,44 +1,295 @@
+use std::ops::Deref;
+
 use nannou::prelude::*;
 
 use crate::agent::Agent;
-use crate::cell::Cell;
+use crate::cell::{Cell, MAX_TIMES};
+use crate::influence::InfluenceField;
+use crate::journal::Event;
+use crate::topology::{Boundary, CellCoord, Topology};
 
 pub struct Grid {
     pub n_cols: usize,
     pub n_rows: usize,
     pub cell_size: f32,
     pub cell_spacing: f32,
+    pub topology: Topology,
+    pub max_times: i32, // fills before a cell is blocked
+    pub origin: Vec2,   // where the center of the grid is drawn
+    pub influence: InfluenceField,
     pub cells: Vec<Cell>,
 }
 
 impl Grid {
-    pub fn new(n_cols: usize, n_rows: usize, cell_size: f32, cell_spacing: f32) -> Self {
+    pub fn new(
+        n_cols: usize,
+        n_rows: usize,
+        cell_size: f32,
+        cell_spacing: f32,
+        topology: Topology,
+    ) -> Self {
         let cells: Vec<Cell> = (0..n_cols)
             .flat_map(|col| {
-                (0..n_rows).map(move |row| {
-                    let x =
-                        col as f32 * (cell_size + cell_spacing) - (n_cols as f32 * cell_size / 2.0);
-                    let y =
-                        row as f32 * (cell_size + cell_spacing) - (n_rows as f32 * cell_size / 2.0);
-                    let rect = Rect::from_xy_wh(Vec2::new(x, y), Vec2::new(cell_size, cell_size));
-
-                    Cell::new(row, col, rect)
-                })
+                (0..n_rows)
+                    .map(move |row| Cell::new(CellCoord::new(row, col), Rect::from_w_h(0.0, 0.0)))
             })
             .collect();
 
-        Grid {
+        let mut grid = Grid {
             n_cols,
             n_rows,
             cell_size,
             cell_spacing,
+            topology,
+            max_times: MAX_TIMES,
+            origin: Vec2::ZERO,
+            influence: InfluenceField::default(),
             cells,
+        };
+
+        grid.layout();
+        grid
+    }
+
+    // scale the cells so the whole grid fits the viewport, keeping the simulation untouched
+    pub fn fit(&mut self, viewport: Vec2) {
+        self.fit_into(Rect::from_wh(viewport));
+    }
+
+    // like `fit`, but into a part of the window, e.g. one of several regions side by side
+    pub fn fit_into(&mut self, area: Rect) {
+        // an empty grid has nothing to scale
+        if self.n_cols == 0 || self.n_rows == 0 {
+            return;
+        }
+
+        let pitch = (area.w() / self.n_cols as f32).min(area.h() / self.n_rows as f32);
+        let spacing_ratio = self.cell_spacing / (self.cell_size + self.cell_spacing);
+
+        self.cell_size = pitch * (1.0 - spacing_ratio);
+        self.cell_spacing = pitch * spacing_ratio;
+        self.origin = area.xy();
+        self.layout();
+    }
+
+    // let settled land give off influence for another epoch
+    pub fn spread_influence(&mut self) {
+        let mut influence = std::mem::take(&mut self.influence);
+        influence.spread(self);
+        self.influence = influence;
+    }
+
+    pub fn influence(&self, coord: CellCoord) -> f32 {
+        self.influence.get(self.index(coord))
+    }
+
+    // the area the cells cover, spacing included
+    pub fn bounds(&self) -> Rect {
+        let pitch = self.cell_size + self.cell_spacing;
+        Rect::from_xy_wh(
+            self.origin,
+            Vec2::new(self.n_cols as f32 * pitch, self.n_rows as f32 * pitch),
+        )
+    }
+
+    // place every cell's rect, centering the grid on its origin
+    fn layout(&mut self) {
+        let pitch = self.cell_size + self.cell_spacing;
+        let width = self.n_cols as f32 * pitch;
+        let height = self.n_rows as f32 * pitch;
+
+        for cell in self.cells.iter_mut() {
+            let x = self.origin.x + (cell.coord.col as f32 + 0.5) * pitch - width / 2.0;
+            let y = self.origin.y + (cell.coord.row as f32 + 0.5) * pitch - height / 2.0;
+
+            cell.rect =
+                Rect::from_xy_wh(Vec2::new(x, y), Vec2::new(self.cell_size, self.cell_size));
         }
     }
 
-    pub fn fill(&mut self, row: usize, column: usize, agent: &Agent) {
-        let index = row * self.n_cols + column;
+    // the cell whose rect contains a point in window coordinates, None over the spacing
+    pub fn cell_at(&self, point: Vec2) -> Option<CellCoord> {
+        let pitch = self.cell_size + self.cell_spacing;
+        let local = point - self.origin;
+        let col = (local.x + self.n_cols as f32 * pitch / 2.0) / pitch;
+        let row = (local.y + self.n_rows as f32 * pitch / 2.0) / pitch;
+
+        if col < 0.0 || row < 0.0 || col >= self.n_cols as f32 || row >= self.n_rows as f32 {
+            return None;
+        }
+
+        let coord = CellCoord::new(row as usize, col as usize);
+        self.cell(coord).rect.contains(point).then_some(coord)
+    }
+
+    // cells are laid out column by column, see `new`
+    pub fn index(&self, coord: CellCoord) -> usize {
+        coord.col * self.n_rows + coord.row
+    }
+
+    pub fn coord(&self, index: usize) -> CellCoord {
+        CellCoord::new(index % self.n_rows, index / self.n_rows)
+    }
+
+    pub fn cell(&self, coord: CellCoord) -> &Cell {
+        &self.cells[self.index(coord)]
+    }
+
+    pub fn cell_mut(&mut self, coord: CellCoord) -> &mut Cell {
+        let index = self.index(coord);
+        &mut self.cells[index]
+    }
+
+    // returns what changed, if anything; the terrain can lower the fill cap
+    pub fn fill(&mut self, coord: CellCoord, agent: &Agent) -> Option<Event> {
+        let max_times = self.max_times;
+        let cell = self.cell_mut(coord);
+        let max_times = cell.terrain.max_times(max_times);
+
+        cell.fill(agent.id, max_times)
+    }
+
+    // the cell `d_row` rows and `d_col` columns away, if the topology has one
+    pub fn offset(&self, coord: CellCoord, d_row: isize, d_col: isize) -> Option<CellCoord> {
+        let row = coord.row as isize + d_row;
+        let col = coord.col as isize + d_col;
+        let (n_rows, n_cols) = (self.n_rows as isize, self.n_cols as isize);
+
+        match self.topology.boundary {
+            Boundary::Bounded => {
+                let inside = (0..n_rows).contains(&row) && (0..n_cols).contains(&col);
+                inside.then(|| CellCoord::new(row as usize, col as usize))
+            }
+            Boundary::Toroidal => Some(CellCoord::new(
+                row.rem_euclid(n_rows) as usize,
+                col.rem_euclid(n_cols) as usize,
+            )),
+        }
+    }
+
+    pub fn neighbors(&self, coord: CellCoord) -> Neighbors {
+        let mut neighbors = Neighbors {
+            coords: [coord; 8],
+            len: 0,
+        };
+
+        // tiny toroidal grids can wrap onto the same cell more than once
+        let wraps =
+            self.topology.boundary == Boundary::Toroidal && (self.n_rows < 3 || self.n_cols < 3);
+
+        for &(d_row, d_col) in self.topology.offsets(coord) {
+            if let Some(neighbor) = self.offset(coord, d_row, d_col) {
+                if !wraps || (neighbor != coord && !neighbors.contains(&neighbor)) {
+                    neighbors.coords[neighbors.len] = neighbor;
+                    neighbors.len += 1;
+                }
+            }
+        }
+
+        neighbors
+    }
+}
+
+// a cell's neighbors, at most the eight of a moore neighborhood; kept off the heap since every
+// agent and every cell asks for them each epoch
+#[derive(Clone, Copy, Debug)]
+pub struct Neighbors {
+    coords: [CellCoord; 8],
+    len: usize,
+}
+
+impl Deref for Neighbors {
+    type Target = [CellCoord];
+
+    fn deref(&self) -> &[CellCoord] {
+        &self.coords[..self.len]
+    }
+}
+
+impl IntoIterator for Neighbors {
+    type Item = CellCoord;
+    type IntoIter = std::iter::Take<std::array::IntoIter<CellCoord, 8>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.coords.into_iter().take(self.len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::Neighborhood;
+
+    fn grid(neighborhood: Neighborhood, boundary: Boundary) -> Grid {
+        Grid::new(12, 12, 16.0, 2.0, Topology::new(neighborhood, boundary))
+    }
+
+    fn grid_12x12() -> Grid {
+        grid(Neighborhood::VonNeumann, Boundary::Bounded)
+    }
+
+    #[test]
+    fn index_and_coord_round_trip() {
+        let grid = Grid::new(7, 5, 16.0, 2.0, Topology::default());
+
+        for (index, cell) in grid.cells.iter().enumerate() {
+            assert_eq!(grid.index(cell.coord), index);
+            assert_eq!(grid.coord(index), cell.coord);
+        }
+
+        // (1, 11) and (11, 1) used to share the same string key
+        let grid = grid_12x12();
+        assert_ne!(
+            grid.index(CellCoord::new(1, 11)),
+            grid.index(CellCoord::new(11, 1))
+        );
+    }
+
+    #[test]
+    fn points_hit_the_cell_under_them() {
+        let mut grid = Grid::new(7, 5, 16.0, 2.0, Topology::default());
+        grid.fit(Vec2::new(700.0, 300.0));
+
+        for cell in grid.cells.iter() {
+            assert_eq!(grid.cell_at(cell.rect.xy()), Some(cell.coord));
+        }
+
+        let corner = grid.cell(CellCoord::new(0, 0)).rect;
+        let gap = Vec2::new(corner.right() + grid.cell_spacing / 2.0, corner.y());
+        assert_eq!(grid.cell_at(gap), None);
+        assert_eq!(grid.cell_at(Vec2::new(-1000.0, 0.0)), None);
+    }
+
+    #[test]
+    fn bounded_corners_have_fewer_neighbors() {
+        let corner = CellCoord::new(0, 0);
+
+        assert_eq!(grid_12x12().neighbors(corner).len(), 2);
+        assert_eq!(
+            grid(Neighborhood::Moore, Boundary::Bounded)
+                .neighbors(corner)
+                .len(),
+            3
+        );
+        assert_eq!(
+            grid(Neighborhood::Hexagonal, Boundary::Bounded)
+                .neighbors(corner)
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn toroidal_edges_wrap_around() {
+        let corner = CellCoord::new(0, 0);
+        let grid = grid(Neighborhood::Moore, Boundary::Toroidal);
+        let neighbors = grid.neighbors(corner);
+
+        assert_eq!(neighbors.len(), 8);
+        assert!(neighbors.contains(&CellCoord::new(11, 11)));
+        assert_eq!(grid.offset(corner, -1, 0), Some(CellCoord::new(11, 0)));
 
-        self.cells[index].fill(agent);
+        let grid = grid_12x12();
+        assert_eq!(grid.offset(corner, -1, 0), None);
     }
 }
@@ -0,0 +1,184 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::cell::{Cell, CellState};
+use crate::grid::Grid;
+use crate::topology::CellCoord;
+
+// how far settled land makes itself felt, and for how long, unless configured otherwise
+pub const DIFFUSION: f32 = 0.5;
+pub const DECAY: f32 = 0.1;
+
+// grids with at least this many cells spread their influence on every core
+const PARALLEL_CELLS: usize = 1 << 16;
+
+// a scalar field every settled cell gives off into, spreading to its neighbours each epoch and
+// fading over time; in the middle of a large settlement it approaches 1 / decay
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InfluenceField {
+    pub diffusion: f32, // 0 to 1, how much of the difference to its neighbours a cell makes up
+    pub decay: f32,     // 0 to 1, the share lost every epoch
+    values: Vec<f32>,   // in grid index order, empty until the first epoch
+}
+
+impl Default for InfluenceField {
+    fn default() -> Self {
+        InfluenceField {
+            diffusion: DIFFUSION,
+            decay: DECAY,
+            values: Vec::new(),
+        }
+    }
+}
+
+impl InfluenceField {
+    pub fn new(diffusion: f32, decay: f32) -> Self {
+        InfluenceField {
+            diffusion: diffusion.clamp(0.0, 1.0),
+            decay: decay.clamp(0.0, 1.0),
+            values: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> f32 {
+        self.values.get(index).copied().unwrap_or(0.0)
+    }
+
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    // e.g. out of a snapshot; an empty list is a field that hasn't spread yet
+    pub fn set_values(&mut self, values: Vec<f32>) {
+        self.values = values;
+    }
+
+    pub fn max(&self) -> f32 {
+        self.values.iter().copied().fold(0.0, f32::max)
+    }
+
+    // one epoch: every cell moves towards the mean of its neighbours, fades, and settled cells
+    // add to it; obstacles hold nothing and pass nothing on. every cell only reads the last
+    // epoch's values, so splitting the work across cores gives the very same field
+    pub fn spread(&mut self, grid: &Grid) {
+        let n_cells = grid.cells.len();
+        let parallel = n_cells >= PARALLEL_CELLS;
+
+        // a byte per cell instead of the whole cell, since every cell is read by its neighbours
+        let source = |cell: &Cell| match cell.state {
+            CellState::Obstacle => Source::Obstacle,
+            CellState::Filled { .. } => Source::Settled,
+            CellState::Empty => Source::Open,
+        };
+        let sources: Vec<Source> = if parallel {
+            grid.cells.par_iter().map(source).collect()
+        } else {
+            grid.cells.iter().map(source).collect()
+        };
+
+        if self.values.len() != n_cells {
+            self.values = vec![0.0; n_cells];
+        }
+
+        let (values, sources) = (&self.values[..], &sources[..]);
+        let (diffusion, decay) = (self.diffusion, self.decay);
+
+        // cells are stored column by column, so each column is worked out on its own
+        let n_rows = grid.n_rows;
+        let column = |(col, next): (usize, &mut [f32])| {
+            for (row, next) in next.iter_mut().enumerate() {
+                let index = col * n_rows + row;
+                let source = match sources[index] {
+                    Source::Obstacle => {
+                        *next = 0.0;
+                        continue;
+                    }
+                    Source::Settled => 1.0,
+                    Source::Open => 0.0,
+                };
+
+                let value = values[index];
+                let (mut sum, mut count) = (0.0, 0);
+                let mut add = |index: usize| {
+                    if sources[index] != Source::Obstacle {
+                        sum += values[index];
+                        count += 1;
+                    }
+                };
+
+                // away from the edges a neighbor is always the same number of cells away in the
+                // list, whatever the topology, so only the border needs `Grid::neighbors`
+                let coord = CellCoord::new(row, col);
+
+                if row > 0 && col > 0 && row + 1 < n_rows && col + 1 < grid.n_cols {
+                    for &(d_row, d_col) in grid.topology.offsets(coord) {
+                        add((index as isize + d_col * n_rows as isize + d_row) as usize);
+                    }
+                } else {
+                    for coord in grid.neighbors(coord) {
+                        add(grid.index(coord));
+                    }
+                }
+
+                let mean = if count > 0 { sum / count as f32 } else { value };
+                let spread = value + diffusion * (mean - value);
+
+                *next = spread * (1.0 - decay) + source;
+            }
+        };
+
+        let mut next = vec![0.0; n_cells];
+
+        if parallel {
+            next.par_chunks_mut(n_rows).enumerate().for_each(column);
+        } else {
+            next.chunks_mut(n_rows).enumerate().for_each(column);
+        }
+
+        self.values = next;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Source {
+    Obstacle,
+    Settled,
+    Open,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::AgentId;
+    use crate::topology::Topology;
+
+    #[test]
+    fn influence_spreads_from_settlements_and_fades() {
+        let mut grid = Grid::new(9, 9, 16.0, 2.0, Topology::default());
+        let center = CellCoord::new(4, 4);
+        grid.cell_mut(center).state = CellState::Filled {
+            by: AgentId(0),
+            times: 1,
+            blocked: false,
+        };
+
+        for _ in 0..20 {
+            grid.spread_influence();
+        }
+
+        let at = |grid: &Grid, row, col| grid.influence(CellCoord::new(row, col));
+        assert!(at(&grid, 4, 4) > at(&grid, 4, 5));
+        assert!(at(&grid, 4, 5) > at(&grid, 4, 7));
+        assert!(at(&grid, 4, 7) > 0.0);
+        assert_eq!(at(&grid, 4, 5), at(&grid, 5, 4));
+
+        grid.cell_mut(center).state = CellState::Empty;
+        let before = grid.influence.max();
+
+        for _ in 0..20 {
+            grid.spread_influence();
+        }
+
+        assert!(grid.influence.max() < before * 0.2);
+    }
+}
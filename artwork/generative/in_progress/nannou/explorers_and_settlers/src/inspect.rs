@@ -0,0 +1,133 @@
+use crate::agent::{Agent, Direction};
+use crate::cell::CellState;
+use crate::settlement::Settlements;
+use crate::simulation::{Stats, World};
+use crate::topology::CellCoord;
+
+// the world at a glance, one line each
+pub fn summary(world: &World, fps: f32) -> Vec<String> {
+    let stats = Stats::new(world);
+
+    vec![
+        format!("epoch: {}  seed: {}", world.epoch, world.seed),
+        format!(
+            "agents: {}  settled: {}  exploring: {}",
+            world.agents.len(),
+            stats.settled,
+            stats.exploring
+        ),
+        format!("filled: {}  blocked: {}", stats.filled, stats.blocked),
+        format!("fps: {:.0}", fps),
+    ]
+}
+
+// what's in a cell: its state, land and settlement, then every agent standing on it
+pub fn cell(world: &World, coord: CellCoord, settlements: Option<&Settlements>) -> Vec<String> {
+    let grid = &world.grid;
+    let cell = grid.cell(coord);
+
+    let state = match cell.state {
+        CellState::Empty => "empty".to_string(),
+        CellState::Obstacle => "obstacle".to_string(),
+        CellState::Filled { by, times, blocked } => format!(
+            "filled by {}, {} of {} times{}",
+            by,
+            times,
+            // the terrain can take fewer fills than the grid allows
+            cell.terrain.max_times(grid.max_times),
+            if blocked {
+                format!(", blocked for {} epochs", cell.blocked_for)
+            } else {
+                String::new()
+            }
+        ),
+    };
+
+    let mut lines = vec![
+        format!("row {}, column {}: {}", coord.row, coord.col, state),
+        format!(
+            "attractiveness {:.2}, cost {:.2}, influence {:.2}",
+            cell.terrain.attractiveness,
+            cell.terrain.cost,
+            grid.influence(coord)
+        ),
+    ];
+
+    let settlement =
+        settlements.and_then(|settlements| settlements.get(settlements.at(grid.index(coord))?));
+
+    if let Some(settlement) = settlement {
+        lines.push(format!(
+            "part of {}, {} cells and {} agents",
+            settlement.label(),
+            settlement.area,
+            settlement.population.len()
+        ));
+    }
+
+    lines.extend(world.agents.at(coord).map(agent));
+
+    lines
+}
+
+// e.g. `#12, settled for 4 epochs, 31 epochs old`
+fn agent(agent: &Agent) -> String {
+    let doing = match agent.direction() {
+        Direction::Settle => "settled".to_string(),
+        direction => match agent.route() {
+            Some(route) => format!("heading for {}", route.target),
+            None => format!("exploring {:?}", direction).to_lowercase(),
+        },
+    };
+
+    format!(
+        "{}, {} for {} epochs, {} epochs old",
+        agent.id, doing, agent.history.epochs_in_state, agent.history.age
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+    use crate::rules::Transition;
+    use crate::terrain::Terrain;
+    use crate::topology::Topology;
+
+    #[test]
+    fn cells_are_described_with_whoever_stands_on_them() {
+        let grid = Grid::new(10, 8, 16.0, 2.0, Topology::default());
+        let mut world = World::with_density(grid, 3, 0.0);
+        let coord = CellCoord::new(2, 5);
+        let id =
+            world
+                .agents
+                .spawn_with(&mut world.grid, coord, Transition::Settle { intensity: 5 });
+
+        let lines = cell(&world, coord, None);
+        assert_eq!(
+            lines[0],
+            format!("row 2, column 5: filled by {}, 1 of 6 times", id)
+        );
+        assert_eq!(
+            lines[2],
+            format!("{}, settled for 0 epochs, 0 epochs old", id)
+        );
+
+        world.grid.cell_mut(coord).terrain = Terrain::new(0.5, 0.0, 0.5);
+        let lines = cell(&world, coord, None);
+        assert_eq!(
+            lines[0],
+            format!("row 2, column 5: filled by {}, 1 of 3 times", id)
+        );
+
+        let lines = cell(&world, CellCoord::new(0, 0), None);
+        assert_eq!(lines[0], "row 0, column 0: empty");
+        assert_eq!(lines.len(), 2);
+
+        assert_eq!(
+            summary(&world, 60.0)[1],
+            "agents: 1  settled: 1  exploring: 0"
+        );
+    }
+}
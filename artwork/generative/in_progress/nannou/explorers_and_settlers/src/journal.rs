@@ -0,0 +1,192 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent::{Agent, AgentId};
+use crate::cell::CellState;
+use crate::rules::Transition;
+use crate::simulation::World;
+use crate::topology::CellCoord;
+
+// a single change to the world, carrying the new values so it can be replayed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    AgentMoved {
+        id: AgentId,
+        from: CellCoord,
+        to: CellCoord,
+    },
+    CellFilled {
+        coord: CellCoord,
+        by: AgentId,
+        times: i32, // 1 means the cell was just claimed
+    },
+    CellBlocked {
+        coord: CellCoord,
+        by: AgentId,
+    },
+    CellFaded {
+        coord: CellCoord,
+        by: AgentId,
+        times: i32, // 0 means the cell is empty again
+    },
+    CellUnblocked {
+        coord: CellCoord,
+        by: AgentId,
+    },
+    AgentRedecided {
+        id: AgentId,
+        transition: Transition,
+    },
+    AgentBorn {
+        parent: AgentId,
+        agent: Box<Agent>, // as it was born, its first cell is filled by the next event
+    },
+    AgentsMet {
+        explorer: AgentId,
+        owner: AgentId, // of the land the explorer walked into or next to
+    },
+    AgentLeft {
+        id: AgentId, // through a portal, taking nothing of its land along
+    },
+    AgentArrived {
+        agent: Box<Agent>, // from another region, its first cell is filled by the next event
+    },
+}
+
+impl Event {
+    // the owner and cell, if this event is an agent claiming an empty cell
+    pub fn claimed(&self) -> Option<(AgentId, CellCoord)> {
+        match *self {
+            Event::CellFilled {
+                coord,
+                by,
+                times: 1,
+            } => Some((by, coord)),
+            _ => None,
+        }
+    }
+
+    // replays the change; agents' countdowns and the rng aren't journaled,
+    // so a replayed world is meant for looking at, not for running further
+    pub fn apply(&self, world: &mut World) {
+        match *self {
+            Event::AgentMoved { id, to, .. } => {
+                if let Some(agent) = world.agents.get_mut(id) {
+                    agent.position = to;
+                }
+            }
+            Event::CellFilled { coord, by, times } => {
+                world.grid.cell_mut(coord).state = CellState::Filled {
+                    by,
+                    times,
+                    blocked: false,
+                };
+
+                if times == 1 {
+                    world.agents.claim(by, coord);
+                }
+            }
+            Event::CellBlocked { coord, .. } => {
+                if let CellState::Filled { blocked, .. } = &mut world.grid.cell_mut(coord).state {
+                    *blocked = true;
+                }
+            }
+            Event::CellFaded { coord, times, .. } => {
+                let state = &mut world.grid.cell_mut(coord).state;
+
+                if times <= 0 {
+                    *state = CellState::Empty;
+                    world.agents.release(coord);
+                } else if let CellState::Filled { times: filled, .. } = state {
+                    *filled = times;
+                }
+            }
+            Event::CellUnblocked { coord, .. } => {
+                if let CellState::Filled { blocked, .. } = &mut world.grid.cell_mut(coord).state {
+                    *blocked = false;
+                }
+            }
+            Event::AgentRedecided { id, transition } => {
+                if let Some(agent) = world.agents.get_mut(id) {
+                    agent.apply(transition);
+                }
+            }
+            Event::AgentBorn { ref agent, .. } => world.agents.insert(Agent::clone(agent)),
+            Event::AgentsMet { explorer, owner } => world.social.meet(explorer, owner),
+            Event::AgentLeft { id } => {
+                world.agents.remove(&mut world.grid, id);
+                world.social.forget(id);
+            }
+            Event::AgentArrived { ref agent } => world.agents.insert(Agent::clone(agent)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub epoch: usize,
+    pub event: Event,
+}
+
+// every event of a run, in the order they happened
+#[derive(Default)]
+pub struct Journal {
+    entries: Vec<Entry>,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Journal::default()
+    }
+
+    pub fn record(&mut self, epoch: usize, events: Vec<Event>) {
+        self.entries
+            .extend(events.into_iter().map(|event| Entry { epoch, event }));
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    // entries tagged with an epoch in `after + 1 ..= until`
+    pub fn between(&self, after: usize, until: usize) -> &[Entry] {
+        let start = self.entries.partition_point(|entry| entry.epoch <= after);
+        let end = self.entries.partition_point(|entry| entry.epoch <= until);
+
+        &self.entries[start..end.max(start)]
+    }
+
+    pub fn last_epoch(&self) -> Option<usize> {
+        self.entries.last().map(|entry| entry.epoch)
+    }
+
+    // one json entry per line
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        for entry in self.entries.iter() {
+            serde_json::to_writer(&mut writer, entry)?;
+            writer.write_all(b"\n")?;
+        }
+
+        writer.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut journal = Journal::new();
+
+        for line in reader.lines() {
+            let line = line?;
+
+            if !line.trim().is_empty() {
+                journal.entries.push(serde_json::from_str(&line)?);
+            }
+        }
+
+        Ok(journal)
+    }
+}
@@ -0,0 +1,24 @@
+pub mod agent;
+pub mod cell;
+pub mod chronicle;
+pub mod clock;
+pub mod config;
+pub mod edit;
+pub mod grid;
+pub mod influence;
+pub mod inspect;
+pub mod journal;
+pub mod metrics;
+pub mod path;
+pub mod personality;
+pub mod region;
+pub mod registry;
+pub mod render;
+pub mod rules;
+pub mod settlement;
+pub mod simulation;
+pub mod snapshot;
+pub mod social;
+pub mod terrain;
+pub mod timeline;
+pub mod topology;
@@ -1,28 +1,56 @@
-use cell::CellState;
 use nannou::color::*;
 use nannou::prelude::*;
 
-mod agent;
-use crate::agent::Agent;
-
-mod grid;
-use crate::grid::Grid;
-
-mod cell;
+use explorers_and_settlers::agent::{AgentId, Direction};
+use explorers_and_settlers::clock::Clock;
+use explorers_and_settlers::config::{Config, ConfigError, PaletteConfig};
+use explorers_and_settlers::edit::{Editor, Tool};
+use explorers_and_settlers::inspect;
+use explorers_and_settlers::metrics::{self, Flow, Metrics};
+use explorers_and_settlers::region::Atlas;
+use explorers_and_settlers::render::{
+    draw_chronicle, draw_hud, draw_portals, draw_routes, draw_settlements, draw_social_graph,
+    draw_trait_chart, Agents, Animated, BlockedMask, Heatmap, Influence, Landscape, Renderer,
+    Territory,
+};
+use explorers_and_settlers::settlement::Settlements;
+use explorers_and_settlers::simulation::World;
+use explorers_and_settlers::snapshot::Snapshot;
+use explorers_and_settlers::timeline::Timeline;
+use explorers_and_settlers::topology::Topology;
+
+const CONFIG_PATH: &str = "config.toml";
+const SNAPSHOT_PATH: &str = "snapshot.json";
+const JOURNAL_PATH: &str = "journal.jsonl";
+const METRICS_PATH: &str = "metrics.csv";
+const TERRITORIES_PATH: &str = "territories.csv";
+const TRAITS_PATH: &str = "traits.csv";
+const MIGRATIONS_PATH: &str = "migrations.csv";
+const GRAPHML_PATH: &str = "social.graphml";
+const DOT_PATH: &str = "social.dot";
 
 struct Model {
     _window: WindowId,
     window_size: Vec2,
-    cell_size: f32,
-    warm_palette: Vec<Hsv>,
-    cool_palette: Vec<Hsv>,
-    muted_warm_palette: Vec<Hsv>,
-    muted_cool_palette: Vec<Hsv>,
-    grid: Grid,
-    cell_spacing: f32,
+    config: Config,
+    renderers: Vec<Box<dyn Renderer>>,
+    renderer: usize, // index of the renderer in use
+    atlas: Atlas,    // every region, side by side
+    focus: usize,    // the region that is recorded, measured, edited and saved
     animation_phase: f32,
-    epoch: usize,
-    agents: Vec<Agent>,
+    clock: Clock, // when epochs run, apart from the frames drawn
+    timeline: Timeline,
+    replay: Option<World>, // an earlier epoch rebuilt from the timeline, shown instead of the focus
+    metrics: Vec<Metrics>, // one entry per epoch since the focus was picked, created or loaded
+    flows: Vec<Flow>,      // migrations between regions, every epoch
+    show_traits: bool,     // chart how the population's personality drifts
+    show_social: bool,     // lines between agents that have met
+    reading: Option<AgentId>, // whose story is shown, in the region in focus
+    settlements: Settlements, // followed in the region in focus
+    show_settlements: bool, // outline and name them
+    show_routes: bool,     // where explorers with a destination are headed
+    show_hud: bool,        // the world at a glance, and what's in the cell under the mouse
+    editor: Editor,
 }
 
 fn main() {
@@ -34,53 +62,111 @@ fn model(app: &App) -> Model {
     let window = app.new_window().view(view).event(event).build().unwrap();
     let window_size = app.window_rect().wh();
 
+    // Config, see config.toml for every parameter
+    let config = load_config();
+
     // Grid
-    let cell_size = 16.0; // Set this to the size of each square.
-    let cell_spacing = 2.0; // Set this to the space between each square.
+    let cell_size = config.grid.cell_size;
+    let topology = Topology::default(); // Set this to change which cells are neighbors.
     let n_cols = (window_size.x / cell_size) as usize;
-    let n_rows = (window_size.y / cell_size) as usize;
-    let mut grid = Grid::new(n_cols, n_rows, cell_size, cell_spacing);
+    let mut n_rows = (window_size.y / cell_size) as usize;
+
+    if topology.needs_even_rows() {
+        n_rows -= n_rows % 2;
+    }
 
     // Color Palettes
-    let num_colors: i32 = 360;
-    let warm_palette: Vec<Hsv> = create_pallete(num_colors, 1.0, 180.0, 0.6, 0.9, 0.6, 0.8);
-    let cool_palette: Vec<Hsv> = create_pallete(num_colors, 181.0, 360.0, 0.6, 0.9, 0.6, 0.8);
-    let muted_warm_palette: Vec<Hsv> = create_pallete(num_colors, 1.0, 180.0, 0.0, 0.1, 0.6, 0.8);
-    let muted_cool_palette: Vec<Hsv> = create_pallete(num_colors, 181.0, 360.0, 0.0, 0.1, 0.6, 0.8);
-
-    // max agents is the number of cells in the grid divided by 10
-    let max_agents = (n_cols * n_rows) / 10;
-
-    // create the agents in random places
-    let agents: Vec<Agent> = (0..max_agents)
-        .map(|_| {
-            let row = random_range(0, n_rows);
-            let col = random_range(0, n_cols);
-            let mut agent = Agent::new(Vec2::new(row as f32, col as f32));
-            agent.settle = random::<bool>();
-
-            grid.fill(row, col, &agent);
-
-            agent
-        })
-        .collect();
+    let palettes = &config.palettes;
+    let warm_palette: Vec<Hsv> = palette_from(palettes.colors, &palettes.warm);
+    let cool_palette: Vec<Hsv> = palette_from(palettes.colors, &palettes.cool);
+    let muted_warm_palette: Vec<Hsv> = palette_from(palettes.colors, &palettes.muted_warm);
+    let muted_cool_palette: Vec<Hsv> = palette_from(palettes.colors, &palettes.muted_cool);
+
+    // Visualization modes, V switches to the next one
+    let renderers: Vec<Box<dyn Renderer>> = vec![
+        Box::new(Animated {
+            warm_palette,
+            cool_palette,
+            muted_warm_palette,
+            muted_cool_palette,
+        }),
+        Box::new(Territory),
+        Box::new(Heatmap),
+        Box::new(Influence),
+        Box::new(BlockedMask),
+        Box::new(Agents),
+        Box::new(Landscape),
+    ];
+
+    // Regions and their agents, a single one filling the window unless the config has more
+    let mut atlas = config
+        .build_atlas(n_cols, n_rows, topology, seed_from_args())
+        .unwrap_or_else(|error| exit_with(error));
+    atlas.fit(window_size);
+    let timeline = Timeline::new(&atlas.regions[0].world);
+    let clock = config.clock();
+    let mut settlements = config.settlements(atlas.regions[0].world.seed);
+    settlements.update(&atlas.regions[0].world);
 
     Model {
         _window: window,
         window_size,
-        cell_size,
-        warm_palette,
-        cool_palette,
-        muted_warm_palette,
-        muted_cool_palette,
-        grid,
-        cell_spacing,
+        config,
+        renderers,
+        renderer: 0,
+        atlas,
+        focus: 0,
         animation_phase: 0.0,
-        epoch: 0,
-        agents,
+        clock,
+        timeline,
+        replay: None,
+        metrics: Vec::new(),
+        flows: Vec::new(),
+        show_traits: false,
+        show_social: false,
+        reading: None,
+        settlements,
+        show_settlements: false,
+        show_routes: false,
+        show_hud: false,
+        editor: Editor::new(),
     }
 }
 
+// the first argument is the seed, e.g. `cargo run -- 42`; a random one is picked otherwise
+fn seed_from_args() -> u64 {
+    std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or_else(random::<u64>)
+}
+
+// the defaults are used when there is no config file, a broken one stops the sketch
+fn load_config() -> Config {
+    if !std::path::Path::new(CONFIG_PATH).exists() {
+        return Config::default();
+    }
+
+    Config::load(CONFIG_PATH).unwrap_or_else(|error| exit_with(error))
+}
+
+fn exit_with(error: ConfigError) -> ! {
+    eprintln!("{}: {}", CONFIG_PATH, error);
+    std::process::exit(1);
+}
+
+fn palette_from(num_colors: i32, palette: &PaletteConfig) -> Vec<Hsv> {
+    create_pallete(
+        num_colors,
+        palette.hue[0],
+        palette.hue[1],
+        palette.saturation[0],
+        palette.saturation[1],
+        palette.value[0],
+        palette.value[1],
+    )
+}
+
 fn create_pallete(
     num_colors: i32,
     min_hue: f32,
@@ -106,86 +192,474 @@ fn create_pallete(
     palette
 }
 
-fn update_model(model: &mut Model) {
-    let window_size = model.window_size;
-    let cell_size = model.cell_size; // Set this to the size of each square in pixels.
-    let cell_spacing = model.cell_spacing;
-    let n_cols = (window_size.x / cell_size) as usize;
-    let n_rows = (window_size.y / cell_size) as usize;
+fn event(app: &App, model: &mut Model, event: WindowEvent) {
+    match event {
+        // only the layout changes, the world keeps running
+        Resized(size) => {
+            model.window_size = size;
+            model.atlas.fit(size);
+
+            if let Some(replay) = &mut model.replay {
+                replay.grid.fit_into(model.atlas.layout(size)[model.focus]);
+            }
+        }
+        KeyPressed(Key::S) => save_snapshot(model),
+        KeyPressed(Key::L) => load_snapshot(model),
+        KeyPressed(Key::J) => save_journal(model),
+        KeyPressed(Key::M) => save_metrics(model),
+        KeyPressed(Key::X) => save_social_graph(model),
+        KeyPressed(Key::G) => model.show_traits = !model.show_traits,
+        KeyPressed(Key::N) => model.show_social = !model.show_social,
+        KeyPressed(Key::B) => model.show_settlements = !model.show_settlements,
+        KeyPressed(Key::R) => model.show_routes = !model.show_routes,
+        KeyPressed(Key::H) => model.show_hud = !model.show_hud,
+        KeyPressed(Key::Space) => model.clock.toggle_pause(),
+        KeyPressed(Key::Period) => model.clock.step(),
+        KeyPressed(Key::F) => model.clock.toggle_fast_forward(),
+        KeyPressed(Key::Equals) => model.clock.faster(),
+        KeyPressed(Key::Minus) => model.clock.slower(),
+        KeyPressed(Key::V) => model.renderer = (model.renderer + 1) % model.renderers.len(),
+        KeyPressed(Key::Tab) => focus(model, (model.focus + 1) % model.atlas.regions.len()),
+        // scrub through the epochs recorded so far, the world waits while replaying
+        KeyPressed(Key::Left) => scrub(model, -1),
+        KeyPressed(Key::Right) => scrub(model, 1),
+        KeyPressed(Key::Return) => model.replay = None,
+        // editing tools, P again turns the next agent to another direction
+        KeyPressed(Key::P) => model.editor.tool = Some(next_placement(model.editor.tool)),
+        KeyPressed(Key::E) => model.editor.tool = Some(Tool::Erase),
+        KeyPressed(Key::O) => model.editor.tool = Some(Tool::Obstacle),
+        KeyPressed(Key::T) => model.editor.tool = Some(Tool::Territory),
+        KeyPressed(Key::C) => model.editor.tool = Some(Tool::Clear),
+        KeyPressed(Key::Escape) => {
+            model.editor.tool = None;
+            model.reading = None;
+        }
+        KeyPressed(Key::Z) => undo(model),
+        // without a tool, clicking an agent or its land opens its story
+        MousePressed(MouseButton::Left) if model.editor.tool.is_none() => read(app, model),
+        MousePressed(MouseButton::Left) => press(app, model),
+        MouseMoved(_) => drag(app, model),
+        MouseReleased(MouseButton::Left) => {
+            let world = &mut model.atlas.regions[model.focus].world;
+            let coord = world.grid.cell_at(app.mouse.position());
+
+            if model.editor.release(world, coord) {
+                edited(model);
+            }
+        }
+        _ => (),
+    }
+}
 
-    model.grid = Grid::new(n_cols, n_rows, cell_size, cell_spacing);
+// settled, then every direction in turn
+fn next_placement(tool: Option<Tool>) -> Tool {
+    let direction = match tool {
+        Some(Tool::Place(Direction::Settle)) => Direction::Left,
+        Some(Tool::Place(Direction::Left)) => Direction::Right,
+        Some(Tool::Place(Direction::Right)) => Direction::Up,
+        Some(Tool::Place(Direction::Up)) => Direction::Down,
+        _ => Direction::Settle,
+    };
+
+    Tool::Place(direction)
+}
 
-    // max agents is the number of cells in the grid divided by 10
-    let max_agents = (n_cols * n_rows) / 10;
+// the live world of the region in focus
+fn focused(model: &Model) -> &World {
+    &model.atlas.regions[model.focus].world
+}
 
-    // create the agents in random places
-    let agents: Vec<Agent> = (0..max_agents)
-        .map(|_| {
-            let row = random_range(0, n_rows);
-            let col = random_range(0, n_cols);
-            let mut agent = Agent::new(Vec2::new(row as f32, col as f32));
-            agent.settle = random::<bool>();
+// the part of the window the region in focus is drawn in
+fn focus_area(model: &Model) -> Rect {
+    model.atlas.layout(model.window_size)[model.focus]
+}
 
-            model.grid.fill(row, col, &agent);
+// the timeline, metrics and undo history only follow one region, so they start over
+fn focus(model: &mut Model, region: usize) {
+    if region == model.focus {
+        return;
+    }
 
-            agent
-        })
-        .collect();
+    model.focus = region;
+    model.replay = None;
+    model.reading = None;
+    model.metrics.clear();
+    model.editor.reset();
+    model.settlements = model.config.settlements(focused(model).seed);
+    edited(model);
+}
 
-    model.agents = agents;
+// edits always go to the live world, so pressing while replaying goes back to it;
+// pressing in another region moves the focus there
+fn press(app: &App, model: &mut Model) {
+    let (region, coord) = match model.atlas.cell_at(app.mouse.position()) {
+        Some(hit) if model.editor.tool.is_some() => hit,
+        _ => return,
+    };
+
+    focus(model, region);
+    model.replay = None;
+
+    if model
+        .editor
+        .press(&mut model.atlas.regions[region].world, coord)
+    {
+        edited(model);
+    }
 }
 
-fn event(_app: &App, model: &mut Model, event: WindowEvent) {
-    match event {
-        Resized(size) => {
-            model.window_size = size;
-            update_model(model);
+// strokes stay in the region they started in, and hovering without one changes nothing
+fn drag(app: &App, model: &mut Model) {
+    let coord = match model.atlas.cell_at(app.mouse.position()) {
+        Some((region, coord)) if region == model.focus => coord,
+        _ => return,
+    };
+
+    let world = &mut model.atlas.regions[model.focus].world;
+
+    if model.editor.drag(world, coord) {
+        model.replay = None;
+        edited(model);
+    }
+}
+
+// the agent standing on the clicked cell, or else its owner; anywhere else closes the story
+fn read(app: &App, model: &mut Model) {
+    let (region, coord) = match model.atlas.cell_at(app.mouse.position()) {
+        Some(hit) => hit,
+        None => {
+            model.reading = None;
+            return;
         }
-        _ => (),
+    };
+
+    focus(model, region);
+
+    let world = model.replay.as_ref().unwrap_or_else(|| focused(model));
+    let reading = world
+        .agents
+        .at(coord)
+        .next()
+        .map(|agent| agent.id)
+        .or_else(|| world.grid.cell(coord).owner());
+
+    model.reading = reading;
+}
+
+fn undo(model: &mut Model) {
+    let area = focus_area(model);
+    let world = &mut model.atlas.regions[model.focus].world;
+
+    match model.editor.undo(world) {
+        Ok(true) => {
+            world.grid.fit_into(area);
+            model.replay = None;
+            edited(model);
+        }
+        Ok(false) => (),
+        Err(error) => eprintln!("could not undo: {}", error),
     }
 }
 
-fn update(app: &App, model: &mut Model, _update: Update) {
+// the journal can't replay edits, so the timeline starts over from the edited world
+fn edited(model: &mut Model) {
+    model.timeline = Timeline::new(focused(model));
+    model
+        .settlements
+        .update(&model.atlas.regions[model.focus].world);
+}
+
+fn save_snapshot(model: &Model) {
+    // a snapshot holds a single world, the other regions and their portals would be lost
+    if model.atlas.regions.len() > 1 {
+        eprintln!(
+            "can't save {} regions, snapshots hold a single world",
+            model.atlas.regions.len()
+        );
+        return;
+    }
+
+    let world = focused(model);
+
+    match Snapshot::capture(world).save(SNAPSHOT_PATH) {
+        Ok(()) => println!("saved epoch {} to {}", world.epoch, SNAPSHOT_PATH),
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+// into the region in focus; keeps the current world if the file is missing or broken
+fn load_snapshot(model: &mut Model) {
+    let world = Snapshot::load(SNAPSHOT_PATH).and_then(|snapshot| {
+        let grid = &focused(model).grid;
+        snapshot.restore(grid.cell_size, grid.cell_spacing)
+    });
+
+    match world {
+        Ok(mut world) => {
+            world.grid.fit_into(focus_area(model));
+            println!("loaded epoch {} from {}", world.epoch, SNAPSHOT_PATH);
+            model.timeline = Timeline::new(&world);
+            model.replay = None;
+            model.metrics.clear();
+            model.reading = None;
+            model.editor.reset();
+            model.settlements = model.config.settlements(world.seed);
+            model.settlements.update(&world);
+            model.atlas.regions[model.focus].world = world;
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+fn save_journal(model: &Model) {
+    match model.timeline.journal().save(JOURNAL_PATH) {
+        Ok(()) => println!(
+            "saved journal up to epoch {} to {}",
+            model.timeline.last_epoch(),
+            JOURNAL_PATH
+        ),
+        Err(error) => eprintln!("could not save journal: {}", error),
+    }
+}
+
+fn save_metrics(model: &Model) {
+    let saved = metrics::save_csv(METRICS_PATH, &model.metrics)
+        .and_then(|_| metrics::save_territory_csv(TERRITORIES_PATH, &model.metrics))
+        .and_then(|_| metrics::save_traits_csv(TRAITS_PATH, &model.metrics))
+        .and_then(|_| metrics::save_migration_csv(MIGRATIONS_PATH, &model.flows));
+
+    match saved {
+        Ok(()) => println!(
+            "saved metrics for {} epochs to {}, {}, {} and {}",
+            model.metrics.len(),
+            METRICS_PATH,
+            TERRITORIES_PATH,
+            TRAITS_PATH,
+            MIGRATIONS_PATH
+        ),
+        Err(error) => eprintln!("could not save metrics: {}", error),
+    }
+}
+
+fn save_social_graph(model: &Model) {
+    let world = focused(model);
+    let saved = world
+        .social
+        .save_graphml(GRAPHML_PATH, &world.agents)
+        .and_then(|_| world.social.save_dot(DOT_PATH, &world.agents));
+
+    match saved {
+        Ok(()) => println!(
+            "saved {} encounter edges to {} and {}",
+            world.social.len(),
+            GRAPHML_PATH,
+            DOT_PATH
+        ),
+        Err(error) => eprintln!("could not save social graph: {}", error),
+    }
+}
+
+fn scrub(model: &mut Model, epochs: isize) {
+    let live = focused(model).epoch;
+    let current = model.replay.as_ref().map_or(live, |replay| replay.epoch);
+    let target = (current as isize + epochs).max(model.timeline.first_epoch() as isize) as usize;
+
+    if target >= live {
+        model.replay = None;
+        return;
+    }
+
+    // going forward only needs the next events, going back replays from the start
+    if let Some(replay) = model.replay.as_mut().filter(|replay| replay.epoch < target) {
+        model.timeline.advance(replay, target);
+        return;
+    }
+
+    let grid = &focused(model).grid;
+
+    match model
+        .timeline
+        .replay(target, grid.cell_size, grid.cell_spacing)
+    {
+        Ok(mut replay) => {
+            replay.grid.fit_into(focus_area(model));
+            model.replay = Some(replay);
+        }
+        Err(error) => eprintln!("could not replay epoch {}: {}", target, error),
+    }
+}
+
+// every region moves on together, as many epochs as the clock says are due; while replaying
+// all of them wait. The colours keep oscillating with real time whatever the clock does
+fn update(app: &App, model: &mut Model, update: Update) {
     model.animation_phase = (app.time).sin() / 2.0 + 0.5;
 
-    if app.elapsed_frames() % 10 == 0 {
-        model.epoch += 1;
+    // the clock waits while scrubbing, keeping any pending steps for later
+    if model.replay.is_some() {
+        return;
+    }
 
-        // @TODO update the agents
-        
+    for _ in 0..model.clock.tick(update.since_last) {
+        let mut events = model.atlas.step();
+        let epoch = focused(model).epoch;
+        model
+            .timeline
+            .record(epoch, events.swap_remove(model.focus));
+        model.metrics.push(Metrics::new(focused(model)));
+        model
+            .settlements
+            .update(&model.atlas.regions[model.focus].world);
+        model.flows.extend(model.atlas.flows());
     }
 }
 
 fn view(app: &App, model: &Model, frame: Frame) {
     let draw = app.draw();
+    let renderer = &model.renderers[model.renderer];
+    let several = model.atlas.regions.len() > 1;
 
-    for (i, cell) in model.grid.cells.iter().enumerate() {
-        let mut warm_color;
-        let cool_color;
+    for (index, region) in model.atlas.regions.iter().enumerate() {
+        let world = match &model.replay {
+            Some(replay) if index == model.focus => replay,
+            _ => &region.world,
+        };
 
-        match cell.state {
-            CellState::Empty => {
-                warm_color = model.muted_warm_palette[i % model.muted_warm_palette.len()];
-                cool_color = model.muted_cool_palette[i % model.muted_cool_palette.len()];
-            }
-            CellState::Filled {
-                by: _,
-                times,
-                blocked: _,
-            } => {
-                warm_color = model.warm_palette[i % model.warm_palette.len()];
-                cool_color = model.cool_palette[i % model.cool_palette.len()];
-
-                // change intensity based on how many times it's been filled
-                warm_color.saturation = map_range(times, 0, 5, 0.5, 1.0);
-            }
+        renderer.draw(&draw, world, model.animation_phase);
+
+        if model.show_social {
+            draw_social_graph(&draw, world);
+        }
+
+        if model.show_routes {
+            draw_routes(&draw, world);
         }
 
-        let color = warm_color.mix(&cool_color, model.animation_phase);
+        // they're only followed live, an earlier epoch would need its own history
+        if model.show_settlements && index == model.focus && model.replay.is_none() {
+            draw_settlements(&draw, world, &model.settlements);
+        }
+
+        if several {
+            let bounds = world.grid.bounds();
+            let label = if index == model.focus {
+                format!("[{}]", region.name)
+            } else {
+                region.name.clone()
+            };
+
+            draw.text(&label)
+                .x_y(bounds.x(), bounds.top() + 14.0)
+                .w_h(bounds.w(), 20.0)
+                .color(WHITE);
+        }
+    }
 
-        cell.draw(&draw, color);
+    if several {
+        draw_portals(&draw, &model.atlas);
     }
 
+    let world = model.replay.as_ref().unwrap_or_else(|| focused(model));
+    let window_rect = app.window_rect();
+
+    if model.show_traits {
+        let chart = Rect::from_x_y_w_h(
+            0.0,
+            window_rect.bottom() + 90.0,
+            window_rect.w() - 40.0,
+            160.0,
+        );
+        draw_trait_chart(&draw, chart, &model.metrics);
+    }
+
+    // the story read, next to a ring around whoever it's about
+    if let Some(agent) = model.reading.and_then(|id| world.agents.get(id)) {
+        let rect = world.grid.cell(agent.position).rect;
+        draw.ellipse()
+            .xy(rect.xy())
+            .wh(rect.wh() * 2.0)
+            .no_fill()
+            .stroke(WHITE)
+            .stroke_weight(2.0);
+
+        let panel = Rect::from_x_y_w_h(
+            window_rect.right() - 250.0,
+            window_rect.y(),
+            460.0,
+            window_rect.h() - 80.0,
+        );
+        draw_chronicle(&draw, panel, agent);
+    }
+    let status = match &model.replay {
+        Some(replay) => format!(
+            "seed: {}  view: {}  replaying epoch {} of {}",
+            world.seed,
+            renderer.name(),
+            replay.epoch,
+            model.atlas.regions[model.focus].world.epoch
+        ),
+        None => format!(
+            "seed: {}  view: {}  epoch {}, {}",
+            world.seed,
+            renderer.name(),
+            world.epoch,
+            model.clock.status()
+        ),
+    };
+    let status = match model.editor.tool {
+        Some(tool) => format!("{}  tool: {}", status, tool),
+        None => status,
+    };
+
+    // outline the area a clear is about to empty
+    let hovered = world.grid.cell_at(app.mouse.position());
+
+    if let (Some(start), Some(end)) = (model.editor.region_start(), hovered) {
+        let (a, b) = (world.grid.cell(start).rect, world.grid.cell(end).rect);
+        let (left, right) = (a.left().min(b.left()), a.right().max(b.right()));
+        let (bottom, top) = (a.bottom().min(b.bottom()), a.top().max(b.top()));
+
+        draw.rect()
+            .x_y((left + right) / 2.0, (bottom + top) / 2.0)
+            .w_h(right - left, top - bottom)
+            .no_fill()
+            .stroke(WHITE)
+            .stroke_weight(2.0);
+    }
+
+    if model.show_hud {
+        let mut lines = inspect::summary(world, app.fps());
+
+        if let Some(coord) = hovered {
+            // settlements are only followed live
+            let settlements = Some(&model.settlements).filter(|_| model.replay.is_none());
+
+            lines.push(String::new());
+            lines.extend(inspect::cell(world, coord, settlements));
+
+            let rect = world.grid.cell(coord).rect;
+            draw.rect()
+                .xy(rect.xy())
+                .wh(rect.wh())
+                .no_fill()
+                .stroke(WHITE)
+                .stroke_weight(1.5);
+        }
+
+        let area = Rect::from_x_y_w_h(
+            window_rect.left() + 230.0,
+            window_rect.y() - 20.0,
+            420.0,
+            window_rect.h() - 40.0,
+        );
+        draw_hud(&draw, area, &lines);
+    }
+
+    draw.text(&status)
+        .x_y(window_rect.left() + 310.0, window_rect.top() - 20.0)
+        .w_h(600.0, 20.0)
+        .left_justify()
+        .color(WHITE);
+
     draw.background().color(BLACK);
     draw.to_frame(app, &frame).unwrap();
 }
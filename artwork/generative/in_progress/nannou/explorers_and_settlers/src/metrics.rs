@@ -0,0 +1,293 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::agent::AgentId;
+use crate::cell::CellState;
+use crate::grid::Grid;
+use crate::simulation::World;
+
+pub const CSV_HEADER: &str =
+    "epoch,owned_cells,owners,territory_clusters,largest_cluster,ownership_entropy,blocked_share";
+pub const TERRITORY_CSV_HEADER: &str = "epoch,agent,cells";
+pub const TRAITS_CSV_HEADER: &str = "epoch,agents,restlessness_mean,restlessness_sd,\
+sociability_mean,sociability_sd,attachment_mean,attachment_sd";
+pub const MIGRATION_CSV_HEADER: &str = "epoch,from,to,departed,arrived,travelling";
+
+// how segregated and mobile the population is at one epoch
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metrics {
+    pub epoch: usize,
+    pub territories: BTreeMap<AgentId, usize>, // cells owned by each agent that owns any
+    pub territory_clusters: usize,             // connected groups of cells with the same owner
+    pub largest_cluster: usize,
+    pub ownership_entropy: f64, // in bits, 0 when a single agent owns everything
+    pub blocked_share: f64,     // blocked cells over all cells
+    pub agents: usize,
+    pub trait_means: [f32; 3],   // in the order of Traits::NAMES
+    pub trait_spreads: [f32; 3], // standard deviations
+}
+
+impl Metrics {
+    pub fn new(world: &World) -> Self {
+        let grid = &world.grid;
+        let mut territories: BTreeMap<AgentId, usize> = BTreeMap::new();
+        let mut blocked = 0;
+
+        for cell in grid.cells.iter() {
+            if let CellState::Filled {
+                by,
+                blocked: is_blocked,
+                ..
+            } = cell.state
+            {
+                *territories.entry(by).or_default() += 1;
+
+                if is_blocked {
+                    blocked += 1;
+                }
+            }
+        }
+
+        let clusters = clusters(grid, true);
+        let territory_clusters = clusters.len();
+        let largest_cluster = clusters.iter().map(Vec::len).max().unwrap_or(0);
+
+        let owned: usize = territories.values().sum();
+        let ownership_entropy = territories
+            .values()
+            .map(|&cells| {
+                let p = cells as f64 / owned as f64;
+                -p * p.log2()
+            })
+            .sum::<f64>()
+            .max(0.0);
+
+        // how the population's personality is spread out
+        let agents = world.agents.len();
+        let mut trait_means = [0.0; 3];
+        let mut trait_spreads = [0.0; 3];
+
+        if agents > 0 {
+            for agent in world.agents.iter() {
+                for (sum, value) in trait_means.iter_mut().zip(agent.traits.values()) {
+                    *sum += value;
+                }
+            }
+
+            trait_means = trait_means.map(|sum| sum / agents as f32);
+
+            for agent in world.agents.iter() {
+                let deviations = agent.traits.values().into_iter().zip(trait_means);
+
+                for (sum, (value, mean)) in trait_spreads.iter_mut().zip(deviations) {
+                    *sum += (value - mean).powi(2);
+                }
+            }
+
+            trait_spreads = trait_spreads.map(|sum| (sum / agents as f32).sqrt());
+        }
+
+        Metrics {
+            epoch: world.epoch,
+            territories,
+            territory_clusters,
+            largest_cluster,
+            ownership_entropy,
+            blocked_share: blocked as f64 / grid.cells.len().max(1) as f64,
+            agents,
+            trait_means,
+            trait_spreads,
+        }
+    }
+
+    pub fn owned_cells(&self) -> usize {
+        self.territories.values().sum()
+    }
+
+    pub fn write_csv_row(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{:.6},{:.6}",
+            self.epoch,
+            self.owned_cells(),
+            self.territories.len(),
+            self.territory_clusters,
+            self.largest_cluster,
+            self.ownership_entropy,
+            self.blocked_share
+        )
+    }
+
+    pub fn write_traits_row(&self, writer: &mut impl Write) -> io::Result<()> {
+        write!(writer, "{},{}", self.epoch, self.agents)?;
+
+        for (mean, spread) in self.trait_means.iter().zip(self.trait_spreads.iter()) {
+            write!(writer, ",{:.6},{:.6}", mean, spread)?;
+        }
+
+        writeln!(writer)
+    }
+
+    // one row per agent, long format so it charts easily
+    pub fn write_territory_rows(&self, writer: &mut impl Write) -> io::Result<()> {
+        for (agent, cells) in self.territories.iter() {
+            writeln!(writer, "{},{},{}", self.epoch, agent.0, cells)?;
+        }
+
+        Ok(())
+    }
+}
+
+// flood fill every connected group of owned cells, following the grid's topology, as grid
+// indices; with `same_owner` a group stops where another agent's land starts
+pub fn clusters(grid: &Grid, same_owner: bool) -> Vec<Vec<usize>> {
+    let mut visited = vec![false; grid.cells.len()];
+    let mut clusters = Vec::new();
+
+    for start in 0..grid.cells.len() {
+        let owner = match grid.cells[start].owner() {
+            Some(owner) if !visited[start] => owner,
+            _ => continue,
+        };
+
+        visited[start] = true;
+
+        let mut cells = vec![start];
+        let mut stack = vec![start];
+
+        while let Some(index) = stack.pop() {
+            for neighbor in grid.neighbors(grid.coord(index)) {
+                let neighbor = grid.index(neighbor);
+                let joins = match grid.cells[neighbor].owner() {
+                    Some(other) => !same_owner || other == owner,
+                    None => false,
+                };
+
+                if !visited[neighbor] && joins {
+                    visited[neighbor] = true;
+                    cells.push(neighbor);
+                    stack.push(neighbor);
+                }
+            }
+        }
+
+        clusters.push(cells);
+    }
+
+    clusters
+}
+
+// how many agents moved from one region to another during an epoch
+#[derive(Clone, Debug, PartialEq)]
+pub struct Flow {
+    pub epoch: usize,
+    pub from: String, // region names
+    pub to: String,
+    pub departed: usize,
+    pub arrived: usize,
+    pub travelling: usize, // still on their way at the end of the epoch
+}
+
+impl Flow {
+    pub fn write_csv_row(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{}",
+            self.epoch, self.from, self.to, self.departed, self.arrived, self.travelling
+        )
+    }
+}
+
+pub fn save_csv(path: impl AsRef<Path>, metrics: &[Metrics]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "{}", CSV_HEADER)?;
+
+    for row in metrics {
+        row.write_csv_row(&mut writer)?;
+    }
+
+    writer.flush()
+}
+
+pub fn save_territory_csv(path: impl AsRef<Path>, metrics: &[Metrics]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "{}", TERRITORY_CSV_HEADER)?;
+
+    for row in metrics {
+        row.write_territory_rows(&mut writer)?;
+    }
+
+    writer.flush()
+}
+
+pub fn save_traits_csv(path: impl AsRef<Path>, metrics: &[Metrics]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "{}", TRAITS_CSV_HEADER)?;
+
+    for row in metrics {
+        row.write_traits_row(&mut writer)?;
+    }
+
+    writer.flush()
+}
+
+pub fn save_migration_csv(path: impl AsRef<Path>, flows: &[Flow]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "{}", MIGRATION_CSV_HEADER)?;
+
+    for flow in flows {
+        flow.write_csv_row(&mut writer)?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+    use crate::topology::{CellCoord, Topology};
+
+    #[test]
+    fn counts_settlements_and_entropy() {
+        let mut grid = Grid::new(6, 6, 16.0, 2.0, Topology::default());
+        let owned = [
+            (AgentId(0), CellCoord::new(0, 0)),
+            (AgentId(0), CellCoord::new(0, 1)),
+            (AgentId(0), CellCoord::new(5, 5)), // not touching the other two
+            (AgentId(1), CellCoord::new(3, 3)),
+            (AgentId(1), CellCoord::new(3, 4)),
+            (AgentId(1), CellCoord::new(4, 4)),
+        ];
+
+        for (by, coord) in owned {
+            grid.cell_mut(coord).state = CellState::Filled {
+                by,
+                times: 1,
+                blocked: coord.row == 3,
+            };
+        }
+
+        let mut world = World::with_density(grid, 0, 0.0);
+        world.epoch = 3;
+        let metrics = Metrics::new(&world);
+
+        assert_eq!(metrics.territory_clusters, 3);
+        assert_eq!(metrics.largest_cluster, 3);
+        assert_eq!(metrics.territories[&AgentId(0)], 3);
+        assert!((metrics.ownership_entropy - 1.0).abs() < 1e-9);
+        assert!((metrics.blocked_share - 2.0 / 36.0).abs() < 1e-9);
+
+        // another owner's cell next door joins a settlement, but not a territory
+        let mut grid = world.grid;
+        grid.cell_mut(CellCoord::new(2, 3)).state = CellState::Filled {
+            by: AgentId(0),
+            times: 1,
+            blocked: false,
+        };
+        assert_eq!(clusters(&grid, true).len(), 4);
+        assert_eq!(clusters(&grid, false).len(), 3);
+    }
+}
@@ -0,0 +1,193 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::cell::{Cell, CellState};
+use crate::grid::Grid;
+use crate::topology::{Boundary, CellCoord, Neighborhood};
+
+// cells a search looks at before giving up, so an unreachable goal doesn't cost the whole grid
+pub const MAX_VISITS: usize = 4096;
+
+// a step onto open land, in thousandths so costs can be compared exactly
+const STEP: u64 = 1000;
+
+// whether a path can lead through the cell: not an obstacle and not blocked
+pub fn is_open(cell: &Cell) -> bool {
+    !matches!(
+        cell.state,
+        CellState::Obstacle | CellState::Filled { blocked: true, .. }
+    )
+}
+
+// costly land holds explorers back, so stepping onto it takes as long as they'd expect to wait
+fn step_cost(cell: &Cell) -> u64 {
+    (STEP as f32 / (1.0 - cell.terrain.cost)) as u64
+}
+
+// the fewest steps between two cells under the grid's topology, never more than the real path
+pub fn distance(grid: &Grid, from: CellCoord, to: CellCoord) -> usize {
+    let across = |a: usize, b: usize, n: usize| {
+        let d = a.abs_diff(b);
+
+        match grid.topology.boundary {
+            Boundary::Bounded => d,
+            Boundary::Toroidal => d.min(n - d),
+        }
+    };
+    let rows = across(from.row, to.row, grid.n_rows);
+    let cols = across(from.col, to.col, grid.n_cols);
+
+    match grid.topology.neighborhood {
+        Neighborhood::VonNeumann => rows + cols,
+        // a step changes the row and the column by at most one each
+        Neighborhood::Moore | Neighborhood::Hexagonal => rows.max(cols),
+    }
+}
+
+// the cheapest way from one cell to another through open cells, as the cells to step onto in
+// order; none if `to` isn't open or can't be reached within `MAX_VISITS` cells
+pub fn find(grid: &Grid, from: CellCoord, to: CellCoord) -> Option<Vec<CellCoord>> {
+    if from == to || !is_open(grid.cell(to)) {
+        return None;
+    }
+
+    let estimate = |coord: CellCoord| distance(grid, coord, to) as u64 * STEP;
+    let (start, goal) = (grid.index(from), grid.index(to));
+
+    // by estimated total, then by cost so far, then by index, so ties always break the same way
+    let mut open = BinaryHeap::new();
+    let mut cost: HashMap<usize, u64> = HashMap::new();
+    let mut came_from: HashMap<usize, usize> = HashMap::new();
+    let mut visits = 0;
+
+    open.push(Reverse((estimate(from), 0, start)));
+    cost.insert(start, 0);
+
+    while let Some(Reverse((_, so_far, index))) = open.pop() {
+        if index == goal {
+            let mut path = vec![grid.coord(goal)];
+            let mut at = goal;
+
+            while let Some(&previous) = came_from.get(&at) {
+                if previous == start {
+                    break;
+                }
+
+                path.push(grid.coord(previous));
+                at = previous;
+            }
+
+            path.reverse();
+            return Some(path);
+        }
+
+        // a stale entry, the cell was reached more cheaply since
+        if so_far > cost[&index] {
+            continue;
+        }
+
+        visits += 1;
+
+        if visits > MAX_VISITS {
+            return None;
+        }
+
+        for neighbor in grid.neighbors(grid.coord(index)) {
+            let cell = grid.cell(neighbor);
+
+            if !is_open(cell) {
+                continue;
+            }
+
+            let next = grid.index(neighbor);
+            let through = so_far.saturating_add(step_cost(cell));
+
+            if cost.get(&next).is_some_and(|&known| known <= through) {
+                continue;
+            }
+
+            cost.insert(next, through);
+            came_from.insert(next, index);
+            open.push(Reverse((through + estimate(neighbor), through, next)));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::RuleRegistry;
+    use crate::simulation::World;
+    use crate::terrain::Terrain;
+    use crate::topology::Topology;
+
+    // checks every step is to a neighbor of the last one, and onto open land
+    fn walk(grid: &Grid, from: CellCoord, path: &[CellCoord]) {
+        let mut at = from;
+
+        for &step in path {
+            assert!(grid.neighbors(at).contains(&step));
+            assert!(is_open(grid.cell(step)));
+            at = step;
+        }
+    }
+
+    #[test]
+    fn paths_go_around_obstacles_and_costly_land() {
+        let (from, to) = (CellCoord::new(0, 0), CellCoord::new(0, 8));
+
+        // a wall down column 5 with a gap at the top
+        let mut grid = Grid::new(10, 10, 16.0, 2.0, Topology::default());
+        for row in 0..9 {
+            grid.cell_mut(CellCoord::new(row, 5)).state = CellState::Obstacle;
+        }
+
+        let path = find(&grid, from, to).unwrap();
+        walk(&grid, from, &path);
+        assert_eq!(path.last(), Some(&to));
+        assert!(path.contains(&CellCoord::new(9, 5)));
+
+        // closing the gap leaves no way through
+        grid.cell_mut(CellCoord::new(9, 5)).state = CellState::Obstacle;
+        assert_eq!(find(&grid, from, to), None);
+
+        // a swamp three cells wide is worth a long detour around its end
+        let mut grid = Grid::new(10, 10, 16.0, 2.0, Topology::default());
+        for row in 0..9 {
+            for col in 3..6 {
+                grid.cell_mut(CellCoord::new(row, col)).terrain = Terrain::new(0.5, 0.9, 1.0);
+            }
+        }
+
+        let path = find(&grid, from, to).unwrap();
+        walk(&grid, from, &path);
+        assert_eq!(path.len(), 26);
+        assert_eq!(distance(&grid, from, to), 8);
+    }
+
+    #[test]
+    fn explorers_walk_their_routes() {
+        let grid = Grid::new(40, 30, 16.0, 2.0, Topology::default());
+        let mut world = World::new(grid, 11);
+        let rules = RuleRegistry::with_life_events();
+        let mut routed = 0;
+
+        for _ in 0..40 {
+            world.step(&rules);
+
+            for agent in world.agents.iter() {
+                if let Some(route) = agent.route() {
+                    routed += 1;
+
+                    let ahead: Vec<CellCoord> = route.ahead().copied().collect();
+                    assert_eq!(ahead.last(), Some(&route.target));
+                    assert!(distance(&world.grid, agent.position, route.target) <= 12);
+                }
+            }
+        }
+
+        assert!(routed > 0, "nobody headed anywhere");
+    }
+}
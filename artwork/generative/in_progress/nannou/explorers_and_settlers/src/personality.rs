@@ -0,0 +1,115 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// who an agent is, each trait from 0 to 1; 0.5 everywhere behaves like a traitless agent
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Traits {
+    pub restlessness: f32, // less likely to settle, and explores for longer
+    pub sociability: f32,  // heads towards other agents' land rather than away from it
+    pub attachment: f32,   // more likely to settle, stays settled for longer and stays home
+}
+
+impl Default for Traits {
+    fn default() -> Self {
+        Traits {
+            restlessness: 0.5,
+            sociability: 0.5,
+            attachment: 0.5,
+        }
+    }
+}
+
+impl Traits {
+    pub const NAMES: [&'static str; 3] = ["restlessness", "sociability", "attachment"];
+
+    pub fn random(rng: &mut impl Rng) -> Self {
+        Traits {
+            restlessness: rng.gen(),
+            sociability: rng.gen(),
+            attachment: rng.gen(),
+        }
+    }
+
+    // a child's traits: the parent's, each nudged by up to `mutation` either way
+    pub fn inherit(&self, mutation: f32, rng: &mut impl Rng) -> Self {
+        let mut nudge = |value: f32| (value + rng.gen_range(-mutation..=mutation)).clamp(0.0, 1.0);
+
+        Traits {
+            restlessness: nudge(self.restlessness),
+            sociability: nudge(self.sociability),
+            attachment: nudge(self.attachment),
+        }
+    }
+
+    // in the same order as `NAMES`
+    pub fn values(&self) -> [f32; 3] {
+        [self.restlessness, self.sociability, self.attachment]
+    }
+
+    pub fn settle_bias(&self) -> f32 {
+        (self.attachment - self.restlessness) * 0.5
+    }
+
+    // upper bounds for how many epochs a decision lasts, 10 for neutral traits
+    pub fn max_settle_intensity(&self) -> i32 {
+        2 + (self.attachment * 16.0) as i32
+    }
+
+    pub fn max_explore_intensity(&self) -> i32 {
+        2 + (self.restlessness * 16.0) as i32
+    }
+
+    // multipliers for heading into someone else's land or back into one's own
+    pub fn foreign_pull(&self) -> f32 {
+        0.5 + self.sociability
+    }
+
+    pub fn home_pull(&self) -> f32 {
+        0.5 + self.attachment
+    }
+
+    // from -1 for avoiding established communities to 1 for joining them
+    pub fn community_pull(&self) -> f32 {
+        (self.sociability - 0.5) * 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::Grid;
+    use crate::journal::Event;
+    use crate::rules::{Births, RuleRegistry};
+    use crate::simulation::World;
+    use crate::topology::Topology;
+
+    #[test]
+    fn children_stay_close_to_their_parents() {
+        let mut rules = RuleRegistry::new();
+        rules.births = Births {
+            chance: 1.0,
+            mutation: 0.05,
+        };
+
+        let grid = Grid::new(30, 20, 16.0, 2.0, Topology::default());
+        let mut world = World::new(grid, 9);
+        let population = world.agents.len();
+        let mut births = 0;
+
+        for _ in 0..30 {
+            for event in world.step(&rules) {
+                if let Event::AgentBorn { parent, agent } = event {
+                    let parent = world.agents.get(parent).unwrap().traits.values();
+
+                    for (child, parent) in agent.traits.values().iter().zip(parent) {
+                        assert!((child - parent).abs() <= 0.05 + 1e-6);
+                    }
+
+                    births += 1;
+                }
+            }
+        }
+
+        assert!(births > 0);
+        assert_eq!(world.agents.len(), population + births);
+    }
+}
@@ -0,0 +1,349 @@
+use std::collections::BTreeMap;
+
+use nannou::prelude::*;
+
+use crate::agent::{Agent, AgentId};
+use crate::chronicle::Milestone;
+use crate::journal::Event;
+use crate::metrics::Flow;
+use crate::rules::RuleRegistry;
+use crate::simulation::World;
+use crate::topology::CellCoord;
+
+// space between regions drawn side by side, and above them for their names
+const REGION_GAP: f32 = 20.0;
+const LABEL_HEIGHT: f32 = 30.0;
+
+// a city or a country, with its own grid, agents and rules
+pub struct Region {
+    pub name: String,
+    pub world: World,
+    pub rules: RuleRegistry,
+}
+
+// explorers that walk onto `at` in one region come out at `exit` in another, `delay` epochs later
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Portal {
+    pub from: usize, // region indices
+    pub at: CellCoord,
+    pub to: usize,
+    pub exit: CellCoord,
+    pub delay: usize,
+}
+
+// an agent between regions, it belongs to neither while on its way
+#[derive(Clone, Debug, PartialEq)]
+pub struct Traveller {
+    pub agent: Agent, // with the id it had where it came from
+    pub from: usize,
+    pub to: usize,
+    pub exit: CellCoord,
+    pub arrival: usize, // the epoch it reaches the exit, or later if the exit is blocked
+}
+
+// every region of a run, stepped together
+pub struct Atlas {
+    pub epoch: usize,
+    pub regions: Vec<Region>,
+    pub portals: Vec<Portal>,
+    pub travellers: Vec<Traveller>,
+    departed: BTreeMap<(usize, usize), usize>, // during the last epoch, by from and to
+    arrived: BTreeMap<(usize, usize), usize>,
+}
+
+impl Atlas {
+    pub fn new(regions: Vec<Region>) -> Self {
+        Atlas {
+            epoch: 0,
+            regions,
+            portals: Vec::new(),
+            travellers: Vec::new(),
+            departed: BTreeMap::new(),
+            arrived: BTreeMap::new(),
+        }
+    }
+
+    // a single region without portals, which runs exactly like a plain world
+    pub fn single(name: &str, world: World, rules: RuleRegistry) -> Self {
+        Atlas::new(vec![Region {
+            name: name.to_string(),
+            world,
+            rules,
+        }])
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.regions.iter().position(|region| region.name == name)
+    }
+
+    // step every region, then move agents through portals; returns each region's events
+    pub fn step(&mut self) -> Vec<Vec<Event>> {
+        self.epoch += 1;
+        self.departed.clear();
+        self.arrived.clear();
+
+        let mut events: Vec<Vec<Event>> = self
+            .regions
+            .iter_mut()
+            .map(|region| region.world.step(&region.rules))
+            .collect();
+
+        for (index, region_events) in events.iter_mut().enumerate() {
+            self.depart(index, region_events);
+        }
+
+        self.arrive(&mut events);
+
+        events
+    }
+
+    // agents that stepped onto a portal this epoch leave their region and their land
+    fn depart(&mut self, index: usize, events: &mut Vec<Event>) {
+        let mut leaving = Vec::new();
+
+        for event in events.iter() {
+            if let Event::AgentMoved { id, to, .. } = *event {
+                let portal = self
+                    .portals
+                    .iter()
+                    .find(|portal| portal.from == index && portal.at == to);
+
+                if let Some(portal) = portal {
+                    leaving.push((id, *portal));
+                }
+            }
+        }
+
+        let names: Vec<String> = self
+            .regions
+            .iter()
+            .map(|region| region.name.clone())
+            .collect();
+        let world = &mut self.regions[index].world;
+
+        for (id, portal) in leaving {
+            let mut agent = match world.agents.remove(&mut world.grid, id) {
+                Some(agent) => agent,
+                None => continue,
+            };
+
+            let to = names[portal.to].clone();
+            agent
+                .chronicle
+                .push(self.epoch, Milestone::Departed { at: portal.at, to });
+
+            world.social.forget(id);
+            events.push(Event::AgentLeft { id });
+
+            *self.departed.entry((portal.from, portal.to)).or_default() += 1;
+            self.travellers.push(Traveller {
+                agent,
+                from: portal.from,
+                to: portal.to,
+                exit: portal.exit,
+                arrival: self.epoch + portal.delay,
+            });
+        }
+    }
+
+    // travellers that are due walk out of their exit, unless it's an obstacle for now
+    fn arrive(&mut self, events: &mut [Vec<Event>]) {
+        let epoch = self.epoch;
+        let names: Vec<String> = self
+            .regions
+            .iter()
+            .map(|region| region.name.clone())
+            .collect();
+        let regions = &mut self.regions;
+        let arrived = &mut self.arrived;
+
+        self.travellers.retain(|traveller| {
+            let world = &mut regions[traveller.to].world;
+            let exit = traveller.exit;
+            let open = exit.row < world.grid.n_rows
+                && exit.col < world.grid.n_cols
+                && world.grid.cell(exit).is_passable();
+
+            if traveller.arrival > epoch || !open {
+                return true;
+            }
+
+            let mut agent = traveller.agent.clone();
+            agent.chronicle.push(
+                epoch,
+                Milestone::Arrived {
+                    at: exit,
+                    from: names[traveller.from].clone(),
+                    id: AgentId(world.agents.next_id()),
+                },
+            );
+            events[traveller.to].extend(world.agents.arrive(&mut world.grid, agent, exit));
+            *arrived.entry((traveller.from, traveller.to)).or_default() += 1;
+
+            false
+        });
+    }
+
+    // one row for every pair of regions a portal connects, for the last epoch
+    pub fn flows(&self) -> Vec<Flow> {
+        let mut pairs: Vec<(usize, usize)> = self
+            .portals
+            .iter()
+            .map(|portal| (portal.from, portal.to))
+            .collect();
+        pairs.sort_unstable();
+        pairs.dedup();
+
+        pairs
+            .into_iter()
+            .map(|(from, to)| Flow {
+                epoch: self.epoch,
+                from: self.regions[from].name.clone(),
+                to: self.regions[to].name.clone(),
+                departed: self.departed.get(&(from, to)).copied().unwrap_or(0),
+                arrived: self.arrived.get(&(from, to)).copied().unwrap_or(0),
+                travelling: self
+                    .travellers
+                    .iter()
+                    .filter(|traveller| (traveller.from, traveller.to) == (from, to))
+                    .count(),
+            })
+            .collect()
+    }
+
+    // the part of the viewport each region is drawn in, side by side and as wide as its grid
+    pub fn layout(&self, viewport: Vec2) -> Vec<Rect> {
+        if self.regions.len() == 1 {
+            return vec![Rect::from_wh(viewport)];
+        }
+
+        let gaps = REGION_GAP * (self.regions.len() + 1) as f32;
+        let total_cols: usize = self
+            .regions
+            .iter()
+            .map(|region| region.world.grid.n_cols)
+            .sum();
+        let height = viewport.y - LABEL_HEIGHT * 2.0;
+        let mut left = -viewport.x / 2.0 + REGION_GAP;
+
+        self.regions
+            .iter()
+            .map(|region| {
+                let width =
+                    (viewport.x - gaps) * region.world.grid.n_cols as f32 / total_cols as f32;
+                let area = Rect::from_x_y_w_h(left + width / 2.0, 0.0, width, height);
+                left += width + REGION_GAP;
+
+                area
+            })
+            .collect()
+    }
+
+    pub fn fit(&mut self, viewport: Vec2) {
+        let areas = self.layout(viewport);
+
+        for (region, area) in self.regions.iter_mut().zip(areas) {
+            region.world.grid.fit_into(area);
+        }
+    }
+
+    // the region and cell under a point in window coordinates
+    pub fn cell_at(&self, point: Vec2) -> Option<(usize, CellCoord)> {
+        self.regions
+            .iter()
+            .enumerate()
+            .find_map(|(index, region)| Some((index, region.world.grid.cell_at(point)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::Direction;
+    use crate::grid::Grid;
+    use crate::rules::Transition;
+    use crate::topology::Topology;
+
+    fn empty_region(name: &str, n_cols: usize) -> Region {
+        Region {
+            name: name.to_string(),
+            world: World::with_density(
+                Grid::new(n_cols, 5, 16.0, 2.0, Topology::default()),
+                0,
+                0.0,
+            ),
+            rules: RuleRegistry::new(),
+        }
+    }
+
+    #[test]
+    fn explorers_migrate_through_portals_after_a_delay() {
+        let mut atlas = Atlas::new(vec![empty_region("city", 10), empty_region("country", 20)]);
+        atlas.portals.push(Portal {
+            from: 0,
+            at: CellCoord::new(2, 9),
+            to: 1,
+            exit: CellCoord::new(2, 0),
+            delay: 3,
+        });
+
+        let city = &mut atlas.regions[0].world;
+        city.agents.spawn_with(
+            &mut city.grid,
+            CellCoord::new(2, 6),
+            Transition::Explore {
+                direction: Direction::Right,
+                intensity: 20,
+            },
+        );
+
+        // three moves to reach the portal, then three epochs on the way
+        for _ in 0..3 {
+            atlas.step();
+        }
+
+        assert!(atlas.regions[0].world.agents.is_empty());
+        assert_eq!(atlas.travellers.len(), 1);
+        assert_eq!(atlas.flows()[0].departed, 1);
+        assert!(atlas.regions[0]
+            .world
+            .grid
+            .cells
+            .iter()
+            .all(|cell| cell.owner().is_none()));
+
+        for _ in 0..2 {
+            atlas.step();
+        }
+
+        assert!(atlas.regions[1].world.agents.is_empty());
+
+        let events = atlas.step();
+        let flow = &atlas.flows()[0];
+        assert_eq!((flow.from.as_str(), flow.to.as_str()), ("city", "country"));
+        assert_eq!((flow.arrived, flow.travelling), (1, 0));
+
+        let country = &atlas.regions[1].world;
+        let migrant = country.agents.iter().next().unwrap();
+        assert_eq!(migrant.position, CellCoord::new(2, 0));
+        assert_eq!(
+            country.grid.cell(CellCoord::new(2, 0)).owner(),
+            Some(migrant.id)
+        );
+        assert!(matches!(events[1][0], Event::AgentArrived { .. }));
+
+        let story: Vec<String> = migrant
+            .chronicle
+            .chapters()
+            .iter()
+            .map(|chapter| chapter.to_string())
+            .collect();
+        assert_eq!(
+            story,
+            [
+                "epoch 3: left for country through the portal at (2,9)",
+                "epoch 6: arrived at (2,0) from city, known as #0 from then on",
+            ]
+        );
+    }
+}
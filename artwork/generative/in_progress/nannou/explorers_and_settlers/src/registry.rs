@@ -0,0 +1,608 @@
+use std::collections::{BTreeSet, HashMap};
+
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::agent::{Agent, AgentId};
+use crate::cell::{Cell, CellState};
+use crate::grid::Grid;
+use crate::journal::Event;
+use crate::rules::{Decay, RuleRegistry, Transition};
+use crate::simulation::{mix, SimRng};
+use crate::topology::CellCoord;
+
+// cells written together when a buffered epoch's claims are resolved
+const FILL_CHUNK: usize = 1 << 16;
+
+// agents planned together in a buffered epoch, so there's a plan per batch instead of per agent
+const PLAN_BATCH: usize = 1024;
+
+// what a batch of agents means to do in a buffered epoch, worked out against the grid as it was
+#[derive(Default)]
+struct Plan {
+    events: Vec<Event>,
+    claims: Vec<RankedClaim>,
+    children: Vec<(AgentId, Agent)>, // with their parents, without an id until births are handed out
+}
+
+// owns every agent and keeps track of which cells each one has claimed
+#[derive(Default)]
+pub struct AgentRegistry {
+    agents: Vec<Agent>, // sorted by id, since ids only go up
+    next_id: u32,
+    territories: HashMap<AgentId, BTreeSet<CellCoord>>,
+    owners: HashMap<CellCoord, AgentId>,
+}
+
+impl AgentRegistry {
+    pub fn new() -> Self {
+        AgentRegistry::default()
+    }
+
+    // create an agent and claim the cell it was born in
+    pub fn spawn(&mut self, grid: &mut Grid, position: CellCoord, rng: &mut impl Rng) -> AgentId {
+        let agent = Agent::new(AgentId(self.next_id), position, grid, rng);
+        self.add(grid, agent).0
+    }
+
+    // like `spawn`, but the agent starts out with the given decision
+    pub fn spawn_with(
+        &mut self,
+        grid: &mut Grid,
+        position: CellCoord,
+        transition: Transition,
+    ) -> AgentId {
+        let agent = Agent::with_transition(AgentId(self.next_id), position, transition);
+        self.add(grid, agent).0
+    }
+
+    // take in an agent from elsewhere under a new id, returning its arrival and first filling
+    pub fn arrive(&mut self, grid: &mut Grid, mut agent: Agent, position: CellCoord) -> Vec<Event> {
+        agent.id = AgentId(self.next_id);
+        agent.position = position;
+
+        let mut events = vec![Event::AgentArrived {
+            agent: Box::new(agent.clone()),
+        }];
+        events.extend(self.add(grid, agent).1);
+
+        events
+    }
+
+    // also returns the filling of the agent's first cell
+    fn add(&mut self, grid: &mut Grid, agent: Agent) -> (AgentId, Option<Event>) {
+        let id = agent.id;
+        let filled = grid.fill(agent.position, &agent);
+
+        if let Some((owner, coord)) = filled.as_ref().and_then(Event::claimed) {
+            self.claim(owner, coord);
+        }
+
+        self.insert(agent);
+
+        (id, filled)
+    }
+
+    // put an agent back as it was, e.g. when replaying a birth; ownership is left to the grid
+    pub fn insert(&mut self, agent: Agent) {
+        self.next_id = self.next_id.max(agent.id.0 + 1);
+
+        match self
+            .agents
+            .binary_search_by_key(&agent.id, |agent| agent.id)
+        {
+            Ok(index) => self.agents[index] = agent,
+            Err(index) => self.agents.insert(index, agent),
+        }
+    }
+
+    // take an agent out of the world, emptying every cell it owned
+    pub fn remove(&mut self, grid: &mut Grid, id: AgentId) -> Option<Agent> {
+        let index = self.position(id)?;
+        let agent = self.agents.remove(index);
+
+        for coord in self.territories.remove(&id).into_iter().flatten() {
+            self.owners.remove(&coord);
+            grid.cell_mut(coord).state = CellState::Empty;
+        }
+
+        Some(agent)
+    }
+
+    // rebuild a registry around agents loaded from a snapshot, reading ownership off the grid
+    pub fn restore(mut agents: Vec<Agent>, next_id: u32, grid: &Grid) -> Self {
+        agents.sort_by_key(|agent| agent.id);
+
+        let mut registry = AgentRegistry {
+            agents,
+            next_id,
+            ..AgentRegistry::default()
+        };
+
+        for cell in grid.cells.iter() {
+            if let Some(owner) = cell.owner() {
+                registry.claim(owner, cell.coord);
+            }
+        }
+
+        registry
+    }
+
+    // the id the next spawned agent will get
+    pub fn next_id(&self) -> u32 {
+        self.next_id
+    }
+
+    pub fn len(&self) -> usize {
+        self.agents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.agents.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Agent> {
+        self.agents.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Agent> {
+        self.agents.iter_mut()
+    }
+
+    // agents standing on a cell, which can be more than one
+    pub fn at(&self, coord: CellCoord) -> impl Iterator<Item = &Agent> {
+        self.agents
+            .iter()
+            .filter(move |agent| agent.position == coord)
+    }
+
+    pub fn get(&self, id: AgentId) -> Option<&Agent> {
+        let index = self.position(id)?;
+        Some(&self.agents[index])
+    }
+
+    pub fn get_mut(&mut self, id: AgentId) -> Option<&mut Agent> {
+        let index = self.position(id)?;
+        Some(&mut self.agents[index])
+    }
+
+    fn position(&self, id: AgentId) -> Option<usize> {
+        // ids start at 0 and only go up, so nobody is further along than their id, and everybody
+        // is right there until someone leaves
+        let guess = (id.0 as usize).min(self.agents.len().checked_sub(1)?);
+
+        if self.agents[guess].id == id {
+            return Some(guess);
+        }
+
+        self.agents[..guess]
+            .binary_search_by_key(&id, |agent| agent.id)
+            .ok()
+    }
+
+    // every cell the agent owns, in row then column order
+    pub fn territory(&self, id: AgentId) -> impl Iterator<Item = CellCoord> + '_ {
+        self.territories.get(&id).into_iter().flatten().copied()
+    }
+
+    pub fn territory_size(&self, id: AgentId) -> usize {
+        self.territories.get(&id).map_or(0, |cells| cells.len())
+    }
+
+    pub fn owner(&self, coord: CellCoord) -> Option<AgentId> {
+        self.owners.get(&coord).copied()
+    }
+
+    pub fn claim(&mut self, id: AgentId, coord: CellCoord) {
+        if let Some(previous) = self.owners.insert(coord, id) {
+            self.forget(previous, coord);
+        }
+
+        self.territories.entry(id).or_default().insert(coord);
+    }
+
+    pub fn release(&mut self, coord: CellCoord) {
+        if let Some(previous) = self.owners.remove(&coord) {
+            self.forget(previous, coord);
+        }
+    }
+
+    fn forget(&mut self, id: AgentId, coord: CellCoord) {
+        if let Some(cells) = self.territories.get_mut(&id) {
+            cells.remove(&coord);
+
+            if cells.is_empty() {
+                self.territories.remove(&id);
+            }
+        }
+    }
+
+    // update every agent in id order, keeping track of what they claimed;
+    // children born during the epoch start moving in the next one
+    pub fn step(
+        &mut self,
+        grid: &mut Grid,
+        rules: &RuleRegistry,
+        rng: &mut impl Rng,
+    ) -> Vec<Event> {
+        let mut events: Vec<Event> = Vec::new();
+
+        for index in 0..self.agents.len() {
+            let first = events.len();
+            self.agents[index].update(grid, rules, rng, &mut events);
+
+            for (owner, coord) in events[first..].iter().filter_map(Event::claimed) {
+                self.claim(owner, coord);
+            }
+
+            let moved = events[first..]
+                .iter()
+                .any(|event| matches!(event, Event::AgentMoved { .. }));
+
+            if moved {
+                encounters(&self.agents[index], grid, &mut events);
+            }
+
+            let redecided = events[first..]
+                .iter()
+                .any(|event| matches!(event, Event::AgentRedecided { .. }));
+
+            if redecided
+                && self.agents[index].is_settled()
+                && rules.births.chance > 0.0
+                && rng.gen::<f32>() < rules.births.chance
+            {
+                self.give_birth(index, grid, rules.births.mutation, rng, &mut events);
+            }
+        }
+
+        self.decay(grid, rules.decay, false, &mut events);
+
+        events
+    }
+
+    // every agent plans against the grid as it was at the start of the epoch, each with an rng
+    // of its own, and only then are the claims resolved and written; so agents can plan on
+    // every core at once and `parallel` changes nothing but the time it takes
+    pub fn step_buffered(
+        &mut self,
+        grid: &mut Grid,
+        rules: &RuleRegistry,
+        rng: &mut impl Rng,
+        parallel: bool,
+    ) -> Vec<Event> {
+        let seed: u64 = rng.gen();
+        let front: &Grid = grid;
+
+        let plan = |agents: &mut [Agent]| {
+            let mut plan = Plan::default();
+            let mut cells = Vec::new();
+
+            for agent in agents.iter_mut() {
+                let mut rng = agent_rng(seed, agent.id);
+                let first = plan.events.len();
+                cells.clear();
+                agent.plan(front, rules, &mut rng, &mut cells, &mut plan.events);
+
+                let moved = plan.events[first..]
+                    .iter()
+                    .any(|event| matches!(event, Event::AgentMoved { .. }));
+
+                if moved {
+                    encounters(agent, front, &mut plan.events);
+                }
+
+                let redecided = plan.events[first..]
+                    .iter()
+                    .any(|event| matches!(event, Event::AgentRedecided { .. }));
+
+                if redecided
+                    && agent.is_settled()
+                    && rules.births.chance > 0.0
+                    && rng.gen::<f32>() < rules.births.chance
+                {
+                    let child = agent.offspring(front, rules.births.mutation, &mut rng).map(
+                        |(position, traits)| {
+                            Agent::with_traits(AgentId(u32::MAX), position, traits, front, &mut rng)
+                        },
+                    );
+                    plan.children.extend(child.map(|child| (agent.id, child)));
+                }
+
+                plan.claims
+                    .extend(rank_claims(front, seed, agent.id, &cells));
+            }
+
+            // in cell order, so the chunks of the grid can find theirs
+            plan.claims.sort_unstable_by_key(|claim| claim.0);
+            plan
+        };
+
+        let mut plans: Vec<Plan> = if parallel {
+            self.agents.par_chunks_mut(PLAN_BATCH).map(plan).collect()
+        } else {
+            self.agents.chunks_mut(PLAN_BATCH).map(plan).collect()
+        };
+
+        // the plans' events in id order, and the children waiting for an id
+        let mut events = Vec::with_capacity(plans.iter().map(|plan| plan.events.len()).sum());
+        let mut children = Vec::new();
+
+        for plan in plans.iter_mut() {
+            events.append(&mut plan.events);
+            children.append(&mut plan.children);
+        }
+
+        // one claim wins each cell: its owner's if it has one among them, otherwise the one
+        // picked by a hash of the epoch, the cell and the agent, so nobody is always first.
+        // winners never share a cell, so chunks of the grid can be filled independently, each
+        // gathering its own claims out of the batches
+        let max_times = grid.max_times;
+        let fill = |(chunk, cells): (usize, &mut [Cell])| -> Vec<Event> {
+            let start = chunk * FILL_CHUNK;
+            let end = start + cells.len();
+            let mut winners: Vec<Option<(bool, u64, AgentId)>> = vec![None; cells.len()];
+
+            for plan in plans.iter() {
+                let from = plan.claims.partition_point(|claim| claim.0 < start);
+                let to = plan.claims.partition_point(|claim| claim.0 < end);
+
+                for &(index, pick, id) in plan.claims[from..to].iter() {
+                    let stranger = cells[index - start].owner() != Some(id);
+                    let winner = &mut winners[index - start];
+
+                    if winner.is_none_or(|winner| (stranger, pick, id) < winner) {
+                        *winner = Some((stranger, pick, id));
+                    }
+                }
+            }
+
+            cells
+                .iter_mut()
+                .zip(winners)
+                .filter_map(|(cell, winner)| {
+                    let (_, _, id) = winner?;
+                    let max_times = cell.terrain.max_times(max_times);
+
+                    cell.fill(id, max_times)
+                })
+                .collect()
+        };
+
+        let filled: Vec<Vec<Event>> = if parallel {
+            grid.cells
+                .par_chunks_mut(FILL_CHUNK)
+                .enumerate()
+                .map(fill)
+                .collect()
+        } else {
+            grid.cells
+                .chunks_mut(FILL_CHUNK)
+                .enumerate()
+                .map(fill)
+                .collect()
+        };
+
+        // only cells that were empty change hands
+        let first = events.len();
+        events.reserve(filled.iter().map(Vec::len).sum());
+
+        for mut filled in filled {
+            events.append(&mut filled);
+        }
+
+        for (owner, coord) in events[first..].iter().filter_map(Event::claimed) {
+            self.claim(owner, coord);
+        }
+
+        // children take the ids in their parents' order, as long as their cell is still free
+        for (parent, mut child) in children {
+            if grid.cell(child.position).state != CellState::Empty {
+                continue;
+            }
+
+            child.id = AgentId(self.next_id);
+            events.push(Event::AgentBorn {
+                parent,
+                agent: Box::new(child.clone()),
+            });
+            events.extend(self.add(grid, child).1);
+        }
+
+        self.decay(grid, rules.decay, parallel, &mut events);
+
+        events
+    }
+
+    // land fades while its owner is away, and blocked cells open up again after a while
+    fn decay(&mut self, grid: &mut Grid, decay: Decay, parallel: bool, events: &mut Vec<Event>) {
+        if decay.abandon_after > 0 {
+            let away = |agent: &Agent| -> Option<AgentId> {
+                (agent.history.epochs_away >= decay.abandon_after).then_some(agent.id)
+            };
+            let absent: Vec<AgentId> = if parallel {
+                self.agents.par_iter().filter_map(away).collect()
+            } else {
+                self.agents.iter().filter_map(away).collect()
+            };
+
+            for id in absent {
+                let cells: Vec<CellCoord> = self.territory(id).collect();
+
+                for coord in cells {
+                    let faded = grid.cell_mut(coord).fade();
+
+                    if let Some(Event::CellFaded { times: 0, .. }) = faded {
+                        self.release(coord);
+                    }
+
+                    events.extend(faded);
+                }
+            }
+        }
+
+        if decay.unblock_after > 0 {
+            // every cell cools down on its own, so large grids share the work
+            let unblocked: Vec<Event> = if parallel {
+                grid.cells
+                    .par_iter_mut()
+                    .filter_map(|cell| cell.cool_down(decay.unblock_after))
+                    .collect()
+            } else {
+                grid.cells
+                    .iter_mut()
+                    .filter_map(|cell| cell.cool_down(decay.unblock_after))
+                    .collect()
+            };
+
+            events.extend(unblocked);
+        }
+    }
+
+    fn give_birth(
+        &mut self,
+        parent: usize,
+        grid: &mut Grid,
+        mutation: f32,
+        rng: &mut impl Rng,
+        events: &mut Vec<Event>,
+    ) {
+        let parent = &self.agents[parent];
+        let (position, traits) = match parent.offspring(grid, mutation, rng) {
+            Some(offspring) => offspring,
+            None => return,
+        };
+
+        let child = Agent::with_traits(AgentId(self.next_id), position, traits, grid, rng);
+
+        events.push(Event::AgentBorn {
+            parent: parent.id,
+            agent: Box::new(child.clone()),
+        });
+        events.extend(self.add(grid, child).1);
+    }
+}
+
+// a cell, the claim's pick and the claimant's id
+type RankedClaim = (usize, u64, AgentId);
+
+fn rank_claims<'a>(
+    grid: &'a Grid,
+    seed: u64,
+    id: AgentId,
+    claims: &'a [CellCoord],
+) -> impl Iterator<Item = RankedClaim> + 'a {
+    claims.iter().map(move |&coord| {
+        let index = grid.index(coord);
+        let pick = mix(seed ^ mix(index as u64) ^ mix(u64::from(id.0) << 32));
+
+        (index, pick, id)
+    })
+}
+
+// an explorer meets the owners of the cell it walked into and of the cells around it
+fn encounters(explorer: &Agent, grid: &Grid, events: &mut Vec<Event>) {
+    let mut met: Vec<AgentId> = Vec::new();
+
+    for coord in std::iter::once(explorer.position).chain(grid.neighbors(explorer.position)) {
+        match grid.cell(coord).owner() {
+            Some(owner) if owner != explorer.id && !met.contains(&owner) => met.push(owner),
+            _ => (),
+        }
+    }
+
+    events.extend(met.into_iter().map(|owner| Event::AgentsMet {
+        explorer: explorer.id,
+        owner,
+    }));
+}
+
+// the same stream for an agent in a given epoch, whichever thread it's planned on
+fn agent_rng(seed: u64, id: AgentId) -> SimRng {
+    let mut rng = SimRng::seed_from_u64(seed);
+    rng.set_stream(u64::from(id.0));
+    rng
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::Direction;
+    use crate::simulation::{new_rng, World};
+    use crate::topology::Topology;
+
+    #[test]
+    fn ownership_index_matches_the_grid() {
+        let grid = Grid::new(40, 30, 16.0, 2.0, Topology::default());
+        let mut world = World::new(grid, 7);
+        let rules = RuleRegistry::with_life_events();
+
+        for _ in 0..40 {
+            world.step(&rules);
+        }
+
+        let World { grid, agents, .. } = world;
+
+        for cell in grid.cells.iter() {
+            assert_eq!(agents.owner(cell.coord), cell.owner());
+        }
+
+        let owned: usize = agents
+            .iter()
+            .map(|agent| agents.territory_size(agent.id))
+            .sum();
+        let filled = grid
+            .cells
+            .iter()
+            .filter(|cell| cell.owner().is_some())
+            .count();
+        assert_eq!(owned, filled);
+    }
+
+    #[test]
+    fn abandoned_land_fades_and_blocked_cells_reopen() {
+        let mut grid = Grid::new(10, 5, 16.0, 2.0, Topology::default());
+        let mut agents = AgentRegistry::new();
+        let mut rng = new_rng(0);
+        let mut rules = RuleRegistry::new();
+        rules.decay = Decay {
+            abandon_after: 2,
+            unblock_after: 3,
+        };
+
+        // an explorer leaves the cell it was born on, a blocked cell belongs to nobody around
+        let home = CellCoord::new(2, 0);
+        let id = agents.spawn_with(
+            &mut grid,
+            home,
+            Transition::Explore {
+                direction: Direction::Right,
+                intensity: 20,
+            },
+        );
+        let blocked = CellCoord::new(4, 9);
+        grid.cell_mut(blocked).state = CellState::Filled {
+            by: AgentId(99),
+            times: 6,
+            blocked: true,
+        };
+
+        agents.step(&mut grid, &rules, &mut rng);
+        assert_eq!(agents.territory_size(id), 1);
+
+        let events = agents.step(&mut grid, &rules, &mut rng);
+        assert!(events.contains(&Event::CellFaded {
+            coord: home,
+            by: id,
+            times: 0,
+        }));
+        assert_eq!(grid.cell(home).state, CellState::Empty);
+        assert_eq!(agents.owner(home), None);
+
+        let events = agents.step(&mut grid, &rules, &mut rng);
+        assert!(events.contains(&Event::CellUnblocked {
+            coord: blocked,
+            by: AgentId(99),
+        }));
+    }
+}
@@ -0,0 +1,520 @@
+use nannou::color::*;
+use nannou::prelude::*;
+
+use crate::agent::{Agent, AgentId, Direction};
+use crate::cell::CellState;
+use crate::metrics::Metrics;
+use crate::personality::Traits;
+use crate::region::Atlas;
+use crate::settlement::Settlements;
+use crate::simulation::World;
+use crate::topology::CellCoord;
+
+// one way of drawing the world; every mode reads the same grid and agents
+pub trait Renderer {
+    fn name(&self) -> &'static str;
+
+    // `phase` goes back and forth between 0 and 1, for modes that animate
+    fn draw(&self, draw: &Draw, world: &World, phase: f32);
+}
+
+// a stable hue for every agent, spread around the wheel by the golden angle
+pub fn agent_hue(id: AgentId) -> f32 {
+    (id.0 as f32 * 137.508) % 360.0
+}
+
+// how far a cell is along to being blocked, from 0 on its first fill to 1 on its last
+fn fill_level(world: &World, times: i32) -> f32 {
+    let max_times = world.grid.max_times;
+    if max_times <= 1 {
+        return 1.0;
+    }
+
+    map_range(times.clamp(1, max_times), 1, max_times, 0.0, 1.0)
+}
+
+// the original look: palettes by cell index, fading between warm and cool
+pub struct Animated {
+    pub warm_palette: Vec<Hsv>,
+    pub cool_palette: Vec<Hsv>,
+    pub muted_warm_palette: Vec<Hsv>,
+    pub muted_cool_palette: Vec<Hsv>,
+}
+
+impl Renderer for Animated {
+    fn name(&self) -> &'static str {
+        "animated"
+    }
+
+    fn draw(&self, draw: &Draw, world: &World, phase: f32) {
+        for (i, cell) in world.grid.cells.iter().enumerate() {
+            let mut warm_color;
+            let cool_color;
+
+            match cell.state {
+                // obstacles are left out, so they show as holes in every mode
+                CellState::Obstacle => continue,
+                CellState::Empty => {
+                    warm_color = self.muted_warm_palette[i % self.muted_warm_palette.len()];
+                    cool_color = self.muted_cool_palette[i % self.muted_cool_palette.len()];
+                }
+                CellState::Filled { times, .. } => {
+                    warm_color = self.warm_palette[i % self.warm_palette.len()];
+                    cool_color = self.cool_palette[i % self.cool_palette.len()];
+
+                    // change intensity based on how many times it's been filled
+                    warm_color.saturation = 0.5 + fill_level(world, times) * 0.5;
+                }
+            }
+
+            cell.draw(draw, warm_color.mix(&cool_color, phase));
+        }
+    }
+}
+
+// each agent's cells in its own hue, brighter where it filled them more often
+pub struct Territory;
+
+impl Renderer for Territory {
+    fn name(&self) -> &'static str {
+        "territory"
+    }
+
+    fn draw(&self, draw: &Draw, world: &World, _phase: f32) {
+        for cell in world.grid.cells.iter() {
+            let color = match cell.state {
+                CellState::Obstacle => continue,
+                CellState::Empty => hsv(0.0, 0.0, 0.12),
+                CellState::Filled { by, times, .. } => hsv(
+                    agent_hue(by) / 360.0,
+                    0.7,
+                    0.5 + fill_level(world, times) * 0.5,
+                ),
+            };
+
+            cell.draw(draw, color);
+        }
+    }
+}
+
+// how many times each cell was filled, from cold blue to hot red
+pub struct Heatmap;
+
+impl Renderer for Heatmap {
+    fn name(&self) -> &'static str {
+        "heatmap"
+    }
+
+    fn draw(&self, draw: &Draw, world: &World, _phase: f32) {
+        for cell in world.grid.cells.iter() {
+            let color = match cell.state {
+                CellState::Obstacle => continue,
+                CellState::Empty => hsv(0.0, 0.0, 0.05),
+                CellState::Filled { times, .. } => {
+                    let heat = fill_level(world, times);
+                    hsv((240.0 - heat * 240.0) / 360.0, 0.9, 0.4 + heat * 0.6)
+                }
+            };
+
+            cell.draw(draw, color);
+        }
+    }
+}
+
+// the influence field, from dark where nobody is felt to bright yellow in the strongest community
+pub struct Influence;
+
+impl Renderer for Influence {
+    fn name(&self) -> &'static str {
+        "influence"
+    }
+
+    fn draw(&self, draw: &Draw, world: &World, _phase: f32) {
+        let grid = &world.grid;
+        let max = grid.influence.max().max(f32::EPSILON);
+
+        for (index, cell) in grid.cells.iter().enumerate() {
+            if !cell.is_passable() {
+                continue;
+            }
+
+            // the square root keeps the faint edges of a community visible
+            let strength = (grid.influence.get(index) / max).sqrt();
+            cell.draw(
+                draw,
+                hsv(0.75 - strength * 0.6, 0.8, 0.05 + strength * 0.95),
+            );
+        }
+    }
+}
+
+// blocked cells stand out, owned ones are faint and empty ones dark
+pub struct BlockedMask;
+
+impl Renderer for BlockedMask {
+    fn name(&self) -> &'static str {
+        "blocked"
+    }
+
+    fn draw(&self, draw: &Draw, world: &World, _phase: f32) {
+        for cell in world.grid.cells.iter() {
+            let value = match cell.state {
+                CellState::Obstacle => continue,
+                CellState::Empty => 0.05,
+                CellState::Filled { blocked: false, .. } => 0.25,
+                CellState::Filled { blocked: true, .. } => 0.95,
+            };
+
+            cell.draw(draw, hsv(0.0, 0.0, value));
+        }
+    }
+}
+
+// faint territories with a dot on every settled agent and an arrow on every explorer
+pub struct Agents;
+
+impl Renderer for Agents {
+    fn name(&self) -> &'static str {
+        "agents"
+    }
+
+    fn draw(&self, draw: &Draw, world: &World, _phase: f32) {
+        for cell in world.grid.cells.iter() {
+            let color = match cell.state {
+                CellState::Obstacle => continue,
+                CellState::Empty => hsv(0.0, 0.0, 0.08),
+                CellState::Filled { by, .. } => hsv(agent_hue(by) / 360.0, 0.4, 0.3),
+            };
+
+            cell.draw(draw, color);
+        }
+
+        let size = world.grid.cell_size;
+
+        for agent in world.agents.iter() {
+            let center = world.grid.cell(agent.position).rect.xy();
+            let color = hsv(agent_hue(agent.id) / 360.0, 0.8, 1.0);
+
+            let heading = match agent.direction() {
+                Direction::Left => vec2(-1.0, 0.0),
+                Direction::Right => vec2(1.0, 0.0),
+                Direction::Up => vec2(0.0, 1.0),
+                Direction::Down => vec2(0.0, -1.0),
+                Direction::Settle => vec2(0.0, 0.0),
+            };
+
+            if agent.is_settled() || heading == Vec2::ZERO {
+                draw.ellipse().xy(center).radius(size * 0.3).color(color);
+            } else {
+                draw.arrow()
+                    .start(center - heading * size * 0.4)
+                    .end(center + heading * size * 0.4)
+                    .weight(size * 0.15)
+                    .head_length(size * 0.3)
+                    .head_width(size * 0.25)
+                    .color(color);
+            }
+        }
+    }
+}
+
+// the terrain map: brighter is more attractive, redder is costlier to move into
+pub struct Landscape;
+
+impl Renderer for Landscape {
+    fn name(&self) -> &'static str {
+        "landscape"
+    }
+
+    fn draw(&self, draw: &Draw, world: &World, _phase: f32) {
+        for cell in world.grid.cells.iter() {
+            if !cell.is_passable() {
+                continue;
+            }
+
+            let terrain = cell.terrain;
+            let value = 0.15 + terrain.attractiveness * 0.75;
+            let color = hsv(0.3 - terrain.cost / 3.0, 0.2 + terrain.cost * 0.7, value);
+
+            cell.draw(draw, color);
+        }
+    }
+}
+
+// the mean of every trait over the recorded epochs, one line each, with the spread shaded
+pub fn draw_trait_chart(draw: &Draw, area: Rect, history: &[Metrics]) {
+    draw.rect()
+        .xy(area.xy())
+        .wh(area.wh())
+        .color(hsva(0.0, 0.0, 0.0, 0.7));
+
+    if history.len() < 2 {
+        return;
+    }
+
+    let x = |i: usize| area.left() + i as f32 / (history.len() - 1) as f32 * area.w();
+    let y = |value: f32| area.bottom() + value.clamp(0.0, 1.0) * area.h();
+
+    for (t, name) in Traits::NAMES.iter().enumerate() {
+        let hue = t as f32 / Traits::NAMES.len() as f32;
+        let mean = |metrics: &Metrics| metrics.trait_means[t];
+        let spread = |metrics: &Metrics| metrics.trait_spreads[t];
+
+        for (i, metrics) in history.iter().enumerate() {
+            draw.line()
+                .start(vec2(x(i), y(mean(metrics) - spread(metrics))))
+                .end(vec2(x(i), y(mean(metrics) + spread(metrics))))
+                .weight((area.w() / history.len() as f32).max(1.0))
+                .color(hsva(hue, 0.6, 0.8, 0.08));
+        }
+
+        draw.polyline()
+            .weight(2.0)
+            .points(
+                history
+                    .iter()
+                    .enumerate()
+                    .map(|(i, metrics)| vec2(x(i), y(mean(metrics)))),
+            )
+            .color(hsv(hue, 0.6, 1.0));
+
+        draw.text(name)
+            .x_y(area.left() + 60.0, area.top() - 12.0 - t as f32 * 14.0)
+            .w_h(120.0, 14.0)
+            .left_justify()
+            .color(hsv(hue, 0.6, 1.0));
+    }
+}
+
+// a line between every two agents that have met, heavier the more often they did
+pub fn draw_social_graph(draw: &Draw, world: &World) {
+    let center = |id: AgentId| {
+        world
+            .agents
+            .get(id)
+            .map(|agent| world.grid.cell(agent.position).rect.xy())
+    };
+
+    for (a, b, weight) in world.social.edges() {
+        if let (Some(start), Some(end)) = (center(a), center(b)) {
+            draw.line()
+                .start(start)
+                .end(end)
+                .weight(0.5 + (weight as f32).ln())
+                .color(hsva(0.0, 0.0, 1.0, 0.35));
+        }
+    }
+}
+
+// ring every portal's entrance and exit, with a faint line across the gap between regions
+pub fn draw_portals(draw: &Draw, atlas: &Atlas) {
+    for portal in atlas.portals.iter() {
+        let at = atlas.regions[portal.from].world.grid.cell(portal.at).rect;
+        let exit = atlas.regions[portal.to].world.grid.cell(portal.exit).rect;
+
+        draw.line()
+            .start(at.xy())
+            .end(exit.xy())
+            .weight(1.0)
+            .color(hsva(0.0, 0.0, 1.0, 0.15));
+
+        for (rect, hue) in [(at, 0.55), (exit, 0.15)] {
+            draw.ellipse()
+                .xy(rect.xy())
+                .wh(rect.wh())
+                .no_fill()
+                .stroke(hsv(hue, 0.8, 1.0))
+                .stroke_weight(2.0);
+        }
+    }
+}
+
+// where every explorer with a destination is headed: the way ahead and a ring at the end
+pub fn draw_routes(draw: &Draw, world: &World) {
+    let grid = &world.grid;
+
+    for agent in world.agents.iter() {
+        let route = match agent.route() {
+            Some(route) => route,
+            None => continue,
+        };
+        let color = hsva(agent_hue(agent.id) / 360.0, 0.6, 1.0, 0.7);
+        let mut from = grid.cell(agent.position).rect.xy();
+
+        // step by step rather than one polyline, so wrapping around a torus doesn't cut across
+        for &coord in route.ahead() {
+            let to = grid.cell(coord).rect.xy();
+
+            if from.distance(to) < (grid.cell_size + grid.cell_spacing) * 2.0 {
+                draw.line().start(from).end(to).weight(1.5).color(color);
+            }
+
+            from = to;
+        }
+
+        let target = grid.cell(route.target).rect;
+        draw.ellipse()
+            .xy(target.xy())
+            .wh(target.wh())
+            .no_fill()
+            .stroke(color)
+            .stroke_weight(1.5);
+    }
+}
+
+// settlements smaller than this go without a label, so they don't bury the map in text
+const LABELED_AREA: usize = 12;
+
+// a line wherever a settlement meets land that isn't part of it, and a label on the larger ones
+pub fn draw_settlements(draw: &Draw, world: &World, settlements: &Settlements) {
+    let grid = &world.grid;
+    let half = (grid.cell_size + grid.cell_spacing) / 2.0;
+    let color = hsva(0.0, 0.0, 1.0, 0.8);
+
+    for (index, cell) in grid.cells.iter().enumerate() {
+        let here = match settlements.at(index) {
+            Some(id) => id,
+            None => continue,
+        };
+        let center = cell.rect.xy();
+
+        // the side facing each neighbor, as the two corners it runs between
+        let sides = [
+            ((0, -1), vec2(-half, -half), vec2(-half, half)),
+            ((0, 1), vec2(half, -half), vec2(half, half)),
+            ((1, 0), vec2(-half, half), vec2(half, half)),
+            ((-1, 0), vec2(-half, -half), vec2(half, -half)),
+        ];
+
+        for ((d_row, d_col), start, end) in sides {
+            let row = cell.coord.row as isize + d_row;
+            let col = cell.coord.col as isize + d_col;
+            let inside = row >= 0
+                && col >= 0
+                && (row as usize) < grid.n_rows
+                && (col as usize) < grid.n_cols;
+
+            // the edges of the grid count as a border even when it wraps
+            if inside
+                && settlements.at(grid.index(CellCoord::new(row as usize, col as usize)))
+                    == Some(here)
+            {
+                continue;
+            }
+
+            draw.line()
+                .start(center + start)
+                .end(center + end)
+                .weight(1.5)
+                .color(color);
+        }
+    }
+
+    for settlement in settlements.iter() {
+        if settlement.area < LABELED_AREA {
+            continue;
+        }
+
+        let label = format!("{} ({})", settlement.label(), settlement.population.len());
+
+        draw.text(&label)
+            .xy(grid.cell(settlement.center).rect.xy())
+            .w_h(200.0, 16.0)
+            .font_size(12)
+            .color(WHITE);
+    }
+}
+
+// lines of text on a dark panel hanging from the top left corner of `area`, as tall as they need
+pub fn draw_hud(draw: &Draw, area: Rect, lines: &[String]) {
+    let line_height = 16.0;
+    let h = (lines.len() as f32 + 1.0) * line_height;
+    let panel = Rect::from_x_y_w_h(area.x(), area.top() - h / 2.0, area.w(), h);
+
+    draw.rect()
+        .xy(panel.xy())
+        .wh(panel.wh())
+        .color(hsva(0.0, 0.0, 0.0, 0.8));
+
+    for (i, line) in lines.iter().enumerate() {
+        draw.text(line)
+            .x_y(panel.x(), panel.top() - (i as f32 + 1.0) * line_height)
+            .w_h(panel.w() - 20.0, line_height)
+            .font_size(12)
+            .left_justify()
+            .color(hsv(0.0, 0.0, 0.9));
+    }
+}
+
+// an agent's story so far, newest chapters last; the oldest ones give way when it doesn't fit
+pub fn draw_chronicle(draw: &Draw, area: Rect, agent: &Agent) {
+    let line_height = 16.0;
+
+    draw.rect()
+        .xy(area.xy())
+        .wh(area.wh())
+        .color(hsva(0.0, 0.0, 0.0, 0.8));
+
+    let title = format!(
+        "{}, {} epochs old, {} chapters",
+        agent.id,
+        agent.history.age,
+        agent.chronicle.len()
+    );
+    let chapters = agent.chronicle.chapters();
+    // below the title, with a line to spare at the bottom
+    let fits = (area.h() / line_height).max(3.0) as usize - 3;
+    let skipped = chapters.len().saturating_sub(fits);
+    let lines = std::iter::once(title).chain(
+        chapters[skipped..]
+            .iter()
+            .map(|chapter| chapter.to_string()),
+    );
+
+    for (i, line) in lines.enumerate() {
+        let color = if i == 0 {
+            hsv(agent_hue(agent.id) / 360.0, 0.6, 1.0)
+        } else {
+            hsv(0.0, 0.0, 0.9)
+        };
+
+        draw.text(&line)
+            .x_y(area.x(), area.top() - (i as f32 + 1.0) * line_height)
+            .w_h(area.w() - 20.0, line_height)
+            .font_size(12)
+            .left_justify()
+            .color(color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+    use crate::topology::Topology;
+
+    #[test]
+    fn hues_are_stable_and_on_the_wheel() {
+        for id in (0..1000).chain([u32::MAX / 2, u32::MAX]) {
+            let hue = agent_hue(AgentId(id));
+            assert_eq!(hue, agent_hue(AgentId(id)));
+            assert!((0.0..360.0).contains(&hue), "{} for {}", hue, id);
+        }
+
+        assert_ne!(agent_hue(AgentId(1)), agent_hue(AgentId(2)));
+    }
+
+    #[test]
+    fn fill_levels_go_from_first_to_last_fill() {
+        let grid = Grid::new(4, 4, 16.0, 2.0, Topology::default());
+        let mut world = World::with_density(grid, 0, 0.0);
+        world.grid.max_times = 6;
+
+        assert_eq!(fill_level(&world, 1), 0.0);
+        assert_eq!(fill_level(&world, 6), 1.0);
+
+        // a single fill blocks the cell, so it's as full as it gets
+        world.grid.max_times = 1;
+        assert_eq!(fill_level(&world, 1), 1.0);
+    }
+}
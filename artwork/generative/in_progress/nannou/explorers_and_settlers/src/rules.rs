@@ -0,0 +1,386 @@
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::agent::{Agent, Direction};
+use crate::cell::CellState;
+use crate::grid::Grid;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Transition {
+    Settle {
+        intensity: i32,
+    },
+    Explore {
+        direction: Direction,
+        intensity: i32,
+    },
+}
+
+pub struct Proposal {
+    pub transition: Transition,
+    pub weight: f32,
+}
+
+// how the cells around an agent are taken
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Surroundings {
+    pub own: usize,
+    pub foreign: usize,
+    pub empty: usize,
+}
+
+impl Surroundings {
+    pub fn new(agent: &Agent, grid: &Grid) -> Self {
+        let mut surroundings = Surroundings::default();
+
+        for coord in grid.neighbors(agent.position) {
+            match grid.cell(coord).state {
+                CellState::Empty => surroundings.empty += 1,
+                CellState::Obstacle => (),
+                CellState::Filled { by, .. } if by == agent.id => surroundings.own += 1,
+                CellState::Filled { .. } => surroundings.foreign += 1,
+            }
+        }
+
+        surroundings
+    }
+}
+
+// a life decision, e.g. getting married or moving to another country; shared between the
+// threads of a parallel epoch
+pub trait LifeRule: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // return None when the rule doesn't apply to this agent right now
+    fn propose(
+        &self,
+        agent: &Agent,
+        surroundings: &Surroundings,
+        rng: &mut dyn RngCore,
+    ) -> Option<Proposal>;
+}
+
+struct RuleEntry {
+    rule: Box<dyn LifeRule>,
+    enabled: bool,
+    probability: f32, // chance the rule is considered at all when an agent re-decides
+}
+
+// settlers that re-decide can have a child next door, who inherits their traits
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Births {
+    pub chance: f32,   // per settled re-decision
+    pub mutation: f32, // how far each of the child's traits can stray from the parent's
+}
+
+// land fades while its owner is away and blocked cells open up again; 0 turns either off
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Decay {
+    pub abandon_after: u32, // epochs away from its land before an owner's cells lose a fill each epoch
+    pub unblock_after: u32, // epochs a blocked cell stays blocked
+}
+
+// explorers setting off can pick a cell to head for and walk there along the cheapest path
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Destinations {
+    pub chance: f32,   // per re-decision to explore
+    pub radius: usize, // the farthest a destination can be, in steps
+}
+
+#[derive(Default)]
+pub struct RuleRegistry {
+    rules: Vec<RuleEntry>,
+    pub births: Births,
+    pub decay: Decay,
+    pub destinations: Destinations,
+}
+
+impl RuleRegistry {
+    pub fn new() -> Self {
+        RuleRegistry::default()
+    }
+
+    // marriage, job change, relocation, career change and study, all enabled, plus decay and
+    // destinations; nobody is born unless `births` is set, as `Config::rules` does
+    pub fn with_life_events() -> Self {
+        let mut registry = RuleRegistry::new();
+        registry.decay = Decay {
+            abandon_after: 20,
+            unblock_after: 30,
+        };
+        registry.destinations = Destinations {
+            chance: 0.3,
+            radius: 12,
+        };
+
+        registry.register(Box::new(Marriage), 1.0);
+        registry.register(Box::new(JobChange), 0.5);
+        registry.register(Box::new(Relocation), 0.3);
+        registry.register(Box::new(CareerChange), 0.2);
+        registry.register(Box::new(Study), 0.4);
+
+        registry
+    }
+
+    pub fn register(&mut self, rule: Box<dyn LifeRule>, probability: f32) {
+        self.rules.push(RuleEntry {
+            rule,
+            enabled: true,
+            probability: probability.clamp(0.0, 1.0),
+        });
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.rules.iter().map(|entry| entry.rule.name()).collect()
+    }
+
+    // returns false if there's no rule with that name
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.entry_mut(name) {
+            Some(entry) => {
+                entry.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // returns false if there's no rule with that name
+    pub fn set_probability(&mut self, name: &str, probability: f32) -> bool {
+        match self.entry_mut(name) {
+            Some(entry) => {
+                entry.probability = probability.clamp(0.0, 1.0);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn entry_mut(&mut self, name: &str) -> Option<&mut RuleEntry> {
+        self.rules
+            .iter_mut()
+            .find(|entry| entry.rule.name() == name)
+    }
+
+    // ask every enabled rule and pick one of the proposals, favouring heavier ones
+    pub fn decide(&self, agent: &Agent, grid: &Grid, rng: &mut impl Rng) -> Option<Transition> {
+        let surroundings = Surroundings::new(agent, grid);
+        let mut proposals: Vec<Proposal> = Vec::new();
+
+        for entry in self.rules.iter().filter(|entry| entry.enabled) {
+            if rng.gen::<f32>() >= entry.probability {
+                continue;
+            }
+
+            if let Some(proposal) = entry.rule.propose(agent, &surroundings, rng) {
+                if proposal.weight > 0.0 {
+                    proposals.push(proposal);
+                }
+            }
+        }
+
+        if proposals.is_empty() {
+            return None;
+        }
+
+        let total: f32 = proposals.iter().map(|proposal| proposal.weight).sum();
+        let mut pick = rng.gen_range(0.0..total);
+
+        for proposal in proposals.iter() {
+            if pick < proposal.weight {
+                return Some(proposal.transition);
+            }
+
+            pick -= proposal.weight;
+        }
+
+        proposals.last().map(|proposal| proposal.transition)
+    }
+}
+
+// explorers that run into someone else's land settle down for a long while
+pub struct Marriage;
+
+impl LifeRule for Marriage {
+    fn name(&self) -> &'static str {
+        "marriage"
+    }
+
+    fn propose(
+        &self,
+        agent: &Agent,
+        surroundings: &Surroundings,
+        rng: &mut dyn RngCore,
+    ) -> Option<Proposal> {
+        if agent.is_settled() || surroundings.foreign == 0 {
+            return None;
+        }
+
+        Some(Proposal {
+            transition: Transition::Settle {
+                intensity: rng.gen_range(10..20),
+            },
+            weight: surroundings.foreign as f32,
+        })
+    }
+}
+
+// settlers that have been in the same place for a while take a short trip
+pub struct JobChange;
+
+impl LifeRule for JobChange {
+    fn name(&self) -> &'static str {
+        "job-change"
+    }
+
+    fn propose(
+        &self,
+        agent: &Agent,
+        _surroundings: &Surroundings,
+        rng: &mut dyn RngCore,
+    ) -> Option<Proposal> {
+        if !agent.is_settled() || agent.history.epochs_in_state < 10 {
+            return None;
+        }
+
+        Some(Proposal {
+            transition: Transition::Explore {
+                direction: Direction::random(rng),
+                intensity: rng.gen_range(1..3),
+            },
+            weight: 1.0,
+        })
+    }
+}
+
+// crowded settlers leave for a new city or country, far away
+pub struct Relocation;
+
+impl LifeRule for Relocation {
+    fn name(&self) -> &'static str {
+        "relocation"
+    }
+
+    fn propose(
+        &self,
+        agent: &Agent,
+        surroundings: &Surroundings,
+        rng: &mut dyn RngCore,
+    ) -> Option<Proposal> {
+        if !agent.is_settled() || surroundings.foreign < 2 {
+            return None;
+        }
+
+        Some(Proposal {
+            transition: Transition::Explore {
+                direction: Direction::random(rng),
+                intensity: rng.gen_range(10..20),
+            },
+            weight: surroundings.foreign as f32,
+        })
+    }
+}
+
+// after too long doing the same thing, agents do the opposite
+pub struct CareerChange;
+
+impl LifeRule for CareerChange {
+    fn name(&self) -> &'static str {
+        "career-change"
+    }
+
+    fn propose(
+        &self,
+        agent: &Agent,
+        _surroundings: &Surroundings,
+        rng: &mut dyn RngCore,
+    ) -> Option<Proposal> {
+        if agent.history.epochs_in_state < 30 {
+            return None;
+        }
+
+        let intensity = rng.gen_range(5..15);
+        let transition = if agent.is_settled() {
+            Transition::Explore {
+                direction: Direction::random(rng),
+                intensity,
+            }
+        } else {
+            Transition::Settle { intensity }
+        };
+
+        Some(Proposal {
+            transition,
+            weight: agent.history.epochs_in_state as f32 / 30.0,
+        })
+    }
+}
+
+// young explorers stop for a few epochs to learn something new
+pub struct Study;
+
+impl LifeRule for Study {
+    fn name(&self) -> &'static str {
+        "study"
+    }
+
+    fn propose(
+        &self,
+        agent: &Agent,
+        surroundings: &Surroundings,
+        rng: &mut dyn RngCore,
+    ) -> Option<Proposal> {
+        if agent.is_settled() || agent.history.age > 50 || surroundings.empty == 0 {
+            return None;
+        }
+
+        Some(Proposal {
+            transition: Transition::Settle {
+                intensity: rng.gen_range(3..6),
+            },
+            weight: 0.5,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::AgentId;
+    use crate::simulation::new_rng;
+    use crate::topology::{CellCoord, Topology};
+
+    struct AlwaysSettle;
+
+    impl LifeRule for AlwaysSettle {
+        fn name(&self) -> &'static str {
+            "always-settle"
+        }
+
+        fn propose(&self, _: &Agent, _: &Surroundings, _: &mut dyn RngCore) -> Option<Proposal> {
+            Some(Proposal {
+                transition: Transition::Settle { intensity: 7 },
+                weight: 1.0,
+            })
+        }
+    }
+
+    #[test]
+    fn disabled_rules_are_not_consulted() {
+        let mut rng = new_rng(1);
+        let grid = Grid::new(10, 10, 16.0, 2.0, Topology::default());
+        let agent = Agent::new(AgentId(0), CellCoord::new(5, 5), &grid, &mut rng);
+
+        let mut registry = RuleRegistry::new();
+        registry.register(Box::new(AlwaysSettle), 1.0);
+
+        assert_eq!(
+            registry.decide(&agent, &grid, &mut rng),
+            Some(Transition::Settle { intensity: 7 })
+        );
+
+        assert!(registry.set_enabled("always-settle", false));
+        assert_eq!(registry.decide(&agent, &grid, &mut rng), None);
+        assert!(!registry.set_enabled("no-such-rule", false));
+    }
+}
@@ -0,0 +1,391 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::agent::AgentId;
+use crate::metrics;
+use crate::simulation::{mix, World};
+use crate::topology::CellCoord;
+
+pub const CSV_HEADER: &str =
+    "epoch,settlement,name,founded,population,area,growth,merged,split_from";
+
+// syllables generated names are made of, e.g. Kelmoreford
+const STARTS: [&str; 16] = [
+    "al", "bor", "cas", "dun", "el", "fen", "gar", "hol", "ith", "kel", "lan", "mor", "nor", "os",
+    "rav", "tor",
+];
+const MIDDLES: [&str; 8] = ["a", "e", "i", "o", "an", "el", "im", "or"];
+const ENDS: [&str; 12] = [
+    "by", "dale", "ford", "gate", "ham", "holm", "mere", "stead", "ton", "vale", "wick", "worth",
+];
+
+// handed out by the tracker, kept for as long as a settlement lasts and never reused
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SettlementId(pub u32);
+
+impl fmt::Display for SettlementId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "S{}", self.0)
+    }
+}
+
+// a cluster of filled cells joined under the grid's topology, whoever owns them
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settlement {
+    pub id: SettlementId,
+    pub name: Option<String>,
+    pub epoch: usize,                     // when it was last measured
+    pub founded: usize,                   // the epoch it first showed up, or split off
+    pub area: usize,                      // cells
+    pub population: Vec<AgentId>,         // agents standing inside it
+    pub growth: f64, // the change in area since the last epoch, relative to its area then
+    pub merged: Vec<SettlementId>, // settlements that grew into this one during the last epoch
+    pub split_from: Option<SettlementId>, // set in the epoch it broke off another one
+    pub center: CellCoord, // the cell of the settlement closest to its middle, for its label
+}
+
+impl Settlement {
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => self.id.to_string(),
+        }
+    }
+
+    pub fn write_csv_row(&self, writer: &mut impl Write) -> io::Result<()> {
+        let merged: Vec<String> = self.merged.iter().map(|id| id.0.to_string()).collect();
+
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{:.6},{},{}",
+            self.epoch,
+            self.id.0,
+            self.name.as_deref().unwrap_or(""),
+            self.founded,
+            self.population.len(),
+            self.area,
+            self.growth,
+            merged.join(";"),
+            self.split_from.map_or(String::new(), |id| id.0.to_string())
+        )
+    }
+}
+
+// follows settlements from one epoch to the next by the cells they share, so they keep their ids;
+// when several grow into one the largest keeps its id, when one breaks up its largest part does
+pub struct Settlements {
+    seed: Option<u64>, // names are generated from it, none means settlements go by id
+    next_id: u32,
+    settlements: Vec<Settlement>,      // by id
+    vanished: Vec<SettlementId>,       // during the last epoch
+    labels: Vec<Option<SettlementId>>, // the settlement of every cell, in grid index order
+}
+
+impl Settlements {
+    pub fn new() -> Self {
+        Settlements {
+            seed: None,
+            next_id: 0,
+            settlements: Vec::new(),
+            vanished: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+
+    // like `new`, with a generated name for every settlement, different for every seed
+    pub fn with_names(seed: u64) -> Self {
+        Settlements {
+            seed: Some(seed),
+            ..Settlements::new()
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Settlement> {
+        self.settlements.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.settlements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.settlements.is_empty()
+    }
+
+    pub fn get(&self, id: SettlementId) -> Option<&Settlement> {
+        let index = self
+            .settlements
+            .binary_search_by_key(&id, |settlement| settlement.id)
+            .ok()?;
+        Some(&self.settlements[index])
+    }
+
+    // settlements that were there an epoch before and are gone without a trace
+    pub fn vanished(&self) -> &[SettlementId] {
+        &self.vanished
+    }
+
+    // the settlement a cell belongs to, by grid index
+    pub fn at(&self, index: usize) -> Option<SettlementId> {
+        self.labels.get(index).copied().flatten()
+    }
+
+    // find this epoch's settlements and match them with the last ones
+    pub fn update(&mut self, world: &World) {
+        let grid = &world.grid;
+        let n_cells = grid.cells.len();
+
+        // a settlement is land that touches, whoever owns each cell of it
+        let components = metrics::clusters(grid, false);
+        let mut component: Vec<Option<usize>> = vec![None; n_cells];
+
+        for (current, cells) in components.iter().enumerate() {
+            for &index in cells {
+                component[index] = Some(current);
+            }
+        }
+
+        // how many cells every cluster shares with each of the last settlements
+        let mut overlaps: BTreeMap<(usize, SettlementId), usize> = BTreeMap::new();
+
+        for (current, cells) in components.iter().enumerate() {
+            for &index in cells {
+                if let Some(previous) = self.at(index) {
+                    *overlaps.entry((current, previous)).or_default() += 1;
+                }
+            }
+        }
+
+        // every last settlement carries on in the cluster it shares the most cells with
+        let mut heirs: BTreeMap<SettlementId, (usize, usize)> = BTreeMap::new();
+
+        for (&(current, previous), &shared) in overlaps.iter() {
+            let best = heirs.entry(previous).or_insert((shared, current));
+
+            if shared > best.0 {
+                *best = (shared, current);
+            }
+        }
+
+        let mut population: Vec<Vec<AgentId>> = vec![Vec::new(); components.len()];
+
+        for agent in world.agents.iter() {
+            if let Some(current) = component[grid.index(agent.position)] {
+                population[current].push(agent.id);
+            }
+        }
+
+        let last: BTreeMap<SettlementId, Settlement> = std::mem::take(&mut self.settlements)
+            .into_iter()
+            .map(|settlement| (settlement.id, settlement))
+            .collect();
+        let mut labels = vec![None; n_cells];
+
+        for (current, cells) in components.iter().enumerate() {
+            let parents: Vec<(SettlementId, usize)> = overlaps
+                .range((current, SettlementId(0))..=(current, SettlementId(u32::MAX)))
+                .map(|(&(_, previous), &shared)| (previous, shared))
+                .collect();
+            let mut carried: Vec<&Settlement> = parents
+                .iter()
+                .filter(|(previous, _)| heirs[previous].1 == current)
+                .map(|(previous, _)| &last[previous])
+                .collect();
+            // the largest one keeps its id, then the oldest
+            carried.sort_by_key(|settlement| (Reverse(settlement.area), settlement.id));
+
+            let area = cells.len();
+            let (id, name, founded, before, merged, split_from) = match carried.split_first() {
+                Some((kept, others)) => (
+                    kept.id,
+                    kept.name.clone(),
+                    kept.founded,
+                    carried.iter().map(|settlement| settlement.area).sum(),
+                    others.iter().map(|settlement| settlement.id).collect(),
+                    None,
+                ),
+                None => {
+                    let id = SettlementId(self.next_id);
+                    self.next_id += 1;
+
+                    // a piece that broke off, from the settlement it shares the most cells with
+                    let split_from = parents
+                        .iter()
+                        .max_by_key(|&&(previous, shared)| (shared, Reverse(previous)))
+                        .map(|&(previous, _)| previous);
+
+                    (id, self.name(id), world.epoch, 0, Vec::new(), split_from)
+                }
+            };
+
+            for &index in cells {
+                labels[index] = Some(id);
+            }
+
+            self.settlements.push(Settlement {
+                id,
+                name,
+                epoch: world.epoch,
+                founded,
+                area,
+                population: std::mem::take(&mut population[current]),
+                growth: if before > 0 {
+                    (area as f64 - before as f64) / before as f64
+                } else {
+                    0.0
+                },
+                merged,
+                split_from,
+                center: center(world, cells),
+            });
+        }
+
+        self.settlements.sort_by_key(|settlement| settlement.id);
+        self.vanished = last
+            .keys()
+            .filter(|previous| !heirs.contains_key(previous))
+            .copied()
+            .collect();
+        self.labels = labels;
+    }
+
+    fn name(&self, id: SettlementId) -> Option<String> {
+        let hash = mix(self.seed? ^ mix(u64::from(id.0)));
+        let pick = |list: &[&'static str], shift: u32| list[(hash >> shift) as usize % list.len()];
+
+        let name = format!(
+            "{}{}{}",
+            pick(&STARTS, 0),
+            pick(&MIDDLES, 16),
+            pick(&ENDS, 32)
+        );
+        let mut letters = name.chars();
+
+        letters
+            .next()
+            .map(|first| first.to_uppercase().chain(letters).collect())
+    }
+}
+
+impl Default for Settlements {
+    fn default() -> Self {
+        Settlements::new()
+    }
+}
+
+// the cell closest to the middle of a settlement, which is always one of its own
+fn center(world: &World, cells: &[usize]) -> CellCoord {
+    let grid = &world.grid;
+    let n = cells.len() as f32;
+    let (rows, cols) = cells.iter().fold((0.0, 0.0), |(rows, cols), &index| {
+        let coord = grid.coord(index);
+        (rows + coord.row as f32, cols + coord.col as f32)
+    });
+    let (row, col) = (rows / n, cols / n);
+
+    let distance =
+        |coord: CellCoord| (coord.row as f32 - row).powi(2) + (coord.col as f32 - col).powi(2);
+
+    cells
+        .iter()
+        .map(|&index| grid.coord(index))
+        .min_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+        .expect("settlements have at least one cell")
+}
+
+// one row for every settlement at every recorded epoch
+pub fn save_csv(path: impl AsRef<Path>, history: &[Settlement]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "{}", CSV_HEADER)?;
+
+    for settlement in history {
+        settlement.write_csv_row(&mut writer)?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::CellState;
+    use crate::grid::Grid;
+    use crate::rules::Transition;
+    use crate::topology::Topology;
+
+    #[test]
+    fn settlements_keep_their_ids_through_merges_and_splits() {
+        let grid = Grid::new(20, 10, 16.0, 2.0, Topology::default());
+        let mut world = World::with_density(grid, 1, 0.0);
+        let settle = Transition::Settle { intensity: 10 };
+        let west = world
+            .agents
+            .spawn_with(&mut world.grid, CellCoord::new(3, 3), settle);
+        let east = world
+            .agents
+            .spawn_with(&mut world.grid, CellCoord::new(3, 12), settle);
+
+        let fill =
+            |world: &mut World, rows: std::ops::Range<usize>, cols: std::ops::Range<usize>| {
+                let agent = world.agents.get(west).unwrap().clone();
+
+                for row in rows {
+                    for col in cols.clone() {
+                        world.grid.fill(CellCoord::new(row, col), &agent);
+                    }
+                }
+            };
+
+        // two blocks of 3x3 apart
+        fill(&mut world, 2..5, 2..5);
+        fill(&mut world, 2..5, 11..14);
+
+        let mut settlements = Settlements::with_names(1);
+        settlements.update(&world);
+
+        let ids: Vec<SettlementId> = settlements.iter().map(|settlement| settlement.id).collect();
+        assert_eq!(ids, [SettlementId(0), SettlementId(1)]);
+        assert_eq!(settlements.get(SettlementId(1)).unwrap().population, [east]);
+        assert!(settlements
+            .iter()
+            .all(|settlement| settlement.name.is_some()));
+        let names: Vec<Option<String>> = settlements
+            .iter()
+            .map(|settlement| settlement.name.clone())
+            .collect();
+
+        // a road between them makes them one, the west one keeps its id and name
+        world.epoch = 1;
+        fill(&mut world, 3..4, 5..11);
+        settlements.update(&world);
+
+        assert_eq!(settlements.len(), 1);
+        let town = settlements.get(SettlementId(0)).unwrap();
+        assert_eq!(town.area, 24);
+        assert_eq!(town.merged, [SettlementId(1)]);
+        assert_eq!(town.population, [west, east]);
+        assert_eq!(town.name, names[0]);
+        assert!((town.growth - 6.0 / 18.0).abs() < 1e-9);
+        assert_eq!(town.founded, 0);
+
+        // and a gap in the road splits them again, the larger part keeping the id
+        world.epoch = 2;
+        let index = world.grid.index(CellCoord::new(3, 9));
+        world.grid.cells[index].state = CellState::Empty;
+        settlements.update(&world);
+
+        assert_eq!(settlements.len(), 2);
+        assert_eq!(settlements.get(SettlementId(0)).unwrap().area, 13);
+        let split = settlements.get(SettlementId(2)).unwrap();
+        assert_eq!(split.area, 10);
+        assert_eq!(split.split_from, Some(SettlementId(0)));
+        assert_eq!(split.founded, 2);
+        assert_eq!(split.population, [east]);
+        assert_eq!(settlements.at(index), None);
+        assert!(settlements.vanished().is_empty());
+    }
+}
@@ -0,0 +1,299 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::cell::CellState;
+use crate::chronicle;
+use crate::grid::Grid;
+use crate::journal::Event;
+use crate::registry::AgentRegistry;
+use crate::rules::RuleRegistry;
+use crate::social::SocialGraph;
+use crate::topology::CellCoord;
+
+pub struct Stats {
+    pub filled: usize,
+    pub blocked: usize,
+    pub settled: usize,
+    pub exploring: usize,
+}
+
+impl Stats {
+    pub fn new(world: &World) -> Self {
+        let mut filled = 0;
+        let mut blocked = 0;
+
+        for cell in world.grid.cells.iter() {
+            if let CellState::Filled {
+                blocked: is_blocked,
+                ..
+            } = cell.state
+            {
+                filled += 1;
+
+                if is_blocked {
+                    blocked += 1;
+                }
+            }
+        }
+
+        let settled = world
+            .agents
+            .iter()
+            .filter(|agent| agent.is_settled())
+            .count();
+
+        Stats {
+            filled,
+            blocked,
+            settled,
+            exploring: world.agents.len() - settled,
+        }
+    }
+}
+
+// chacha gives the same stream on every platform and can be saved in snapshots
+pub type SimRng = ChaCha8Rng;
+
+// every run is driven by a single rng, so the same seed replays the same world
+pub fn new_rng(seed: u64) -> SimRng {
+    SimRng::seed_from_u64(seed)
+}
+
+// splitmix64's finalizer, to turn nearby numbers into unrelated ones without an rng
+pub fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+// agents per cell when a world is populated
+pub const AGENT_DENSITY: f64 = 0.1;
+
+// how the agents take their turns within an epoch
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Schedule {
+    // one after the other in id order, each seeing what the ones before it did
+    #[default]
+    Sequential,
+    // all against the grid as it was, with clashing claims settled afterwards
+    Buffered,
+    // the buffered schedule spread over every core, with the very same result
+    Parallel,
+}
+
+// everything that changes while the simulation runs
+pub struct World {
+    pub seed: u64,
+    pub epoch: usize,
+    pub grid: Grid,
+    pub agents: AgentRegistry,
+    pub social: SocialGraph, // who has met whom so far
+    pub rng: SimRng,
+    pub schedule: Schedule,
+}
+
+impl World {
+    // populate an empty grid from the seed
+    pub fn new(grid: Grid, seed: u64) -> Self {
+        World::with_density(grid, seed, AGENT_DENSITY)
+    }
+
+    pub fn with_density(mut grid: Grid, seed: u64, density: f64) -> Self {
+        let mut rng = new_rng(seed);
+        let mut agents = populate(&mut grid, density, &mut rng);
+
+        for agent in agents.iter_mut() {
+            chronicle::appear(agent, 0);
+        }
+
+        World {
+            seed,
+            epoch: 0,
+            grid,
+            agents,
+            social: SocialGraph::new(),
+            rng,
+            schedule: Schedule::default(),
+        }
+    }
+
+    // advance the world by one epoch and return everything that happened
+    pub fn step(&mut self, rules: &RuleRegistry) -> Vec<Event> {
+        self.epoch += 1;
+        self.grid.spread_influence();
+
+        let (grid, rng) = (&mut self.grid, &mut self.rng);
+        let events = match self.schedule {
+            Schedule::Sequential => self.agents.step(grid, rules, rng),
+            Schedule::Buffered => self.agents.step_buffered(grid, rules, rng, false),
+            Schedule::Parallel => self.agents.step_buffered(grid, rules, rng, true),
+        };
+
+        for event in events.iter() {
+            if let Event::AgentsMet { explorer, owner } = *event {
+                self.social.meet(explorer, owner);
+            }
+
+            chronicle::write(self, self.epoch, event);
+        }
+
+        events
+    }
+}
+
+// create the agents in random places
+pub fn populate(grid: &mut Grid, density: f64, rng: &mut impl Rng) -> AgentRegistry {
+    // the small margin keeps e.g. 0.1 of 600 cells at 60 agents despite rounding
+    let max_agents = ((grid.n_cols * grid.n_rows) as f64 * density + 1e-9) as usize;
+    let mut agents = AgentRegistry::new();
+
+    for _ in 0..max_agents {
+        let row = rng.gen_range(0..grid.n_rows);
+        let col = rng.gen_range(0..grid.n_cols);
+
+        agents.spawn(grid, CellCoord::new(row, col), rng);
+    }
+
+    agents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Births;
+    use crate::topology::Topology;
+
+    fn run(seed: u64, n_cols: usize, n_rows: usize, epochs: usize) -> Vec<Vec<CellState>> {
+        let grid = Grid::new(n_cols, n_rows, 16.0, 2.0, Topology::default());
+        let mut world = World::new(grid, seed);
+        let rules = RuleRegistry::with_life_events();
+
+        (0..epochs)
+            .map(|_| {
+                world.step(&rules);
+                world
+                    .grid
+                    .cells
+                    .iter()
+                    .map(|cell| cell.state.clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_replays_the_same_world() {
+        let first = run(42, 30, 20, 50);
+        let second = run(42, 30, 20, 50);
+
+        for (epoch, (a, b)) in first.iter().zip(second.iter()).enumerate() {
+            assert_eq!(a, b, "grids diverged at epoch {}", epoch);
+        }
+
+        assert_ne!(first, run(43, 30, 20, 50));
+    }
+
+    fn states(world: &World) -> Vec<CellState> {
+        world
+            .grid
+            .cells
+            .iter()
+            .map(|cell| cell.state.clone())
+            .collect()
+    }
+
+    #[test]
+    fn parallel_epochs_match_the_buffered_ones() {
+        // large enough for the agents to plan in more than one batch and the claims to be
+        // written in more than one chunk
+        let world = |schedule| {
+            let grid = Grid::new(300, 220, 16.0, 2.0, Topology::default());
+            let mut world = World::new(grid, 7);
+            world.schedule = schedule;
+            world
+        };
+        let (mut buffered, mut parallel) = (world(Schedule::Buffered), world(Schedule::Parallel));
+        let mut rules = RuleRegistry::with_life_events();
+        rules.births = Births {
+            chance: 0.05,
+            mutation: 0.1,
+        };
+
+        for epoch in 0..20 {
+            assert_eq!(
+                buffered.step(&rules),
+                parallel.step(&rules),
+                "events diverged at epoch {}",
+                epoch
+            );
+        }
+
+        assert_eq!(states(&buffered), states(&parallel));
+        assert_eq!(buffered.grid.influence, parallel.grid.influence);
+        assert!(buffered.agents.iter().eq(parallel.agents.iter()));
+        assert!(buffered.agents.len() > 6600, "nobody was born");
+    }
+
+    #[test]
+    fn parallel_epochs_do_not_depend_on_the_number_of_threads() {
+        // large enough for the influence to spread on every core too
+        let run = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+
+            pool.install(|| {
+                let grid = Grid::new(260, 260, 16.0, 2.0, Topology::default());
+                let mut world = World::new(grid, 3);
+                world.schedule = Schedule::Parallel;
+                let mut rules = RuleRegistry::with_life_events();
+                rules.births = Births {
+                    chance: 0.05,
+                    mutation: 0.1,
+                };
+
+                let events: Vec<Vec<Event>> = (0..10).map(|_| world.step(&rules)).collect();
+                (events, states(&world), world.grid.influence.clone())
+            })
+        };
+
+        assert!(run(1) == run(4), "one thread and four ran different worlds");
+    }
+
+    // not a check, a measurement: cargo test --release -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn epochs_per_second_on_a_2000_by_2000_grid() {
+        const EPOCHS: usize = 5;
+
+        let mut rules = RuleRegistry::with_life_events();
+        rules.births = Births {
+            chance: 0.05,
+            mutation: 0.1,
+        };
+
+        for schedule in [Schedule::Sequential, Schedule::Parallel] {
+            let grid = Grid::new(2000, 2000, 1.0, 0.0, Topology::default());
+            let mut world = World::new(grid, 1);
+            world.schedule = schedule;
+
+            let start = std::time::Instant::now();
+
+            for _ in 0..EPOCHS {
+                world.step(&rules);
+            }
+
+            let seconds = start.elapsed().as_secs_f64() / EPOCHS as f64;
+            println!(
+                "{:?} on {} threads: {:.2} s per epoch, {:.2} epochs per second",
+                schedule,
+                rayon::current_num_threads(),
+                seconds,
+                1.0 / seconds
+            );
+        }
+    }
+}
@@ -0,0 +1,371 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent::{Agent, AgentId};
+use crate::cell::{CellState, MAX_TIMES};
+use crate::grid::Grid;
+use crate::influence::InfluenceField;
+use crate::registry::AgentRegistry;
+use crate::simulation::{Schedule, SimRng, World};
+use crate::social::SocialGraph;
+use crate::terrain::Terrain;
+use crate::topology::Topology;
+
+// bump this whenever the layout below changes, and teach `migrate` the one before it
+// 1: cells, agents and the rng
+// 2: plus the fill cap, terrain, influence, cooldowns, encounters and schedule, and agents with
+//    traits, a history, a chronicle and a route
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+// the oldest layout `migrate` still knows
+const OLDEST_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    Version(u32),
+    Invalid(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(error) => write!(f, "could not access snapshot: {}", error),
+            SnapshotError::Format(error) => write!(f, "could not parse snapshot: {}", error),
+            SnapshotError::Version(version) => write!(
+                f,
+                "snapshot version {} is not supported, expected {} to {}",
+                version, OLDEST_VERSION, SNAPSHOT_VERSION
+            ),
+            SnapshotError::Invalid(reason) => write!(f, "invalid snapshot: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(error: io::Error) -> Self {
+        SnapshotError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(error: serde_json::Error) -> Self {
+        SnapshotError::Format(error)
+    }
+}
+
+// the whole world at one epoch, saved as json
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub seed: u64,
+    pub epoch: usize,
+    pub n_cols: usize,
+    pub n_rows: usize,
+    pub topology: Topology,
+    #[serde(default = "default_max_times")] // older snapshots used the built-in cap
+    pub max_times: i32,
+    pub cells: Vec<CellState>, // in grid index order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")] // empty when there is no map
+    pub terrain: Vec<Terrain>,
+    #[serde(default)] // older snapshots start from a field that hasn't spread yet
+    pub influence: InfluenceField,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")] // empty when nothing is blocked
+    pub blocked_for: Vec<u32>,
+    pub agents: Vec<Agent>,
+    pub next_id: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")] // the social graph's edges
+    pub encounters: Vec<(AgentId, AgentId, u32)>,
+    pub rng: SimRng, // so a restored run continues exactly like the original
+    #[serde(default)] // older snapshots ran sequentially
+    pub schedule: Schedule,
+}
+
+fn default_max_times() -> i32 {
+    MAX_TIMES
+}
+
+impl Snapshot {
+    pub fn capture(world: &World) -> Self {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            seed: world.seed,
+            epoch: world.epoch,
+            n_cols: world.grid.n_cols,
+            n_rows: world.grid.n_rows,
+            topology: world.grid.topology,
+            max_times: world.grid.max_times,
+            cells: world
+                .grid
+                .cells
+                .iter()
+                .map(|cell| cell.state.clone())
+                .collect(),
+            terrain: if world
+                .grid
+                .cells
+                .iter()
+                .all(|cell| cell.terrain == Terrain::default())
+            {
+                Vec::new()
+            } else {
+                world.grid.cells.iter().map(|cell| cell.terrain).collect()
+            },
+            influence: world.grid.influence.clone(),
+            blocked_for: if world.grid.cells.iter().all(|cell| cell.blocked_for == 0) {
+                Vec::new()
+            } else {
+                world
+                    .grid
+                    .cells
+                    .iter()
+                    .map(|cell| cell.blocked_for)
+                    .collect()
+            },
+            agents: world.agents.iter().cloned().collect(),
+            next_id: world.agents.next_id(),
+            encounters: world.social.edges().collect(),
+            rng: world.rng.clone(),
+            schedule: world.schedule,
+        }
+    }
+
+    // the grid comes back with the given cell size, fit it to a window if needed
+    pub fn restore(self, cell_size: f32, cell_spacing: f32) -> Result<World, SnapshotError> {
+        let snapshot = self.migrate()?;
+        snapshot.restore_current(cell_size, cell_spacing)
+    }
+
+    // bring an older layout up to date, with what its worlds did before the newer fields existed
+    fn migrate(mut self) -> Result<Self, SnapshotError> {
+        if !(OLDEST_VERSION..=SNAPSHOT_VERSION).contains(&self.version) {
+            return Err(SnapshotError::Version(self.version));
+        }
+
+        if self.version == 1 {
+            self.max_times = MAX_TIMES;
+            self.terrain.clear();
+            self.influence = InfluenceField::default();
+            self.blocked_for.clear();
+            self.encounters.clear();
+            self.schedule = Schedule::Sequential;
+            self.version = 2;
+        }
+
+        Ok(self)
+    }
+
+    fn restore_current(self, cell_size: f32, cell_spacing: f32) -> Result<World, SnapshotError> {
+        if self.cells.len() != self.n_cols * self.n_rows {
+            return Err(SnapshotError::Invalid(format!(
+                "{} cells for a {}x{} grid",
+                self.cells.len(),
+                self.n_cols,
+                self.n_rows
+            )));
+        }
+
+        self.topology
+            .fits(self.n_cols, self.n_rows)
+            .map_err(SnapshotError::Invalid)?;
+
+        if !self.terrain.is_empty() && self.terrain.len() != self.cells.len() {
+            return Err(SnapshotError::Invalid(format!(
+                "{} terrain cells for {} cells",
+                self.terrain.len(),
+                self.cells.len()
+            )));
+        }
+
+        if !self.blocked_for.is_empty() && self.blocked_for.len() != self.cells.len() {
+            return Err(SnapshotError::Invalid(format!(
+                "{} blocked counters for {} cells",
+                self.blocked_for.len(),
+                self.cells.len()
+            )));
+        }
+
+        let influence = self.influence.values().len();
+
+        if influence != 0 && influence != self.cells.len() {
+            return Err(SnapshotError::Invalid(format!(
+                "{} influence values for {} cells",
+                influence,
+                self.cells.len()
+            )));
+        }
+
+        if self.max_times < 1 {
+            return Err(SnapshotError::Invalid(format!(
+                "cells can't be filled {} times",
+                self.max_times
+            )));
+        }
+
+        let outside = self
+            .agents
+            .iter()
+            .find(|agent| agent.position.row >= self.n_rows || agent.position.col >= self.n_cols);
+
+        if let Some(agent) = outside {
+            return Err(SnapshotError::Invalid(format!(
+                "agent {} is outside the grid at {}",
+                agent.id, agent.position
+            )));
+        }
+
+        let mut grid = Grid::new(
+            self.n_cols,
+            self.n_rows,
+            cell_size,
+            cell_spacing,
+            self.topology,
+        );
+
+        grid.max_times = self.max_times;
+        grid.influence = self.influence;
+
+        for (cell, state) in grid.cells.iter_mut().zip(self.cells) {
+            cell.state = state;
+        }
+
+        // back into range, a file may hold any number
+        for (cell, terrain) in grid.cells.iter_mut().zip(self.terrain) {
+            cell.terrain = Terrain::new(terrain.attractiveness, terrain.cost, terrain.capacity);
+        }
+
+        for (cell, blocked_for) in grid.cells.iter_mut().zip(self.blocked_for) {
+            cell.blocked_for = blocked_for;
+        }
+
+        let agents = AgentRegistry::restore(self.agents, self.next_id, &grid);
+
+        Ok(World {
+            seed: self.seed,
+            epoch: self.epoch,
+            grid,
+            agents,
+            social: SocialGraph::from_edges(self.encounters),
+            rng: self.rng,
+            schedule: self.schedule,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)?;
+
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        Snapshot::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(json: &str) -> Result<Self, SnapshotError> {
+        // check the version first, so unknown files fail with a useful message
+        let header: serde_json::Value = serde_json::from_str(json)?;
+        let version = header["version"].as_u64().unwrap_or(0) as u32;
+
+        if !(OLDEST_VERSION..=SNAPSHOT_VERSION).contains(&version) {
+            return Err(SnapshotError::Version(version));
+        }
+
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path;
+    use crate::rules::RuleRegistry;
+    use crate::topology::CellCoord;
+
+    #[test]
+    fn restored_world_continues_like_the_original() {
+        let rules = RuleRegistry::with_life_events();
+        let grid = Grid::new(30, 20, 16.0, 2.0, Topology::default());
+        let mut world = World::new(grid, 11);
+
+        for _ in 0..20 {
+            world.step(&rules);
+        }
+
+        let json = serde_json::to_string(&Snapshot::capture(&world)).unwrap();
+        let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+        let mut restored = snapshot.restore(16.0, 2.0).unwrap();
+
+        for _ in 0..20 {
+            world.step(&rules);
+            restored.step(&rules);
+        }
+
+        assert_eq!(restored.epoch, world.epoch);
+
+        for (a, b) in world.grid.cells.iter().zip(restored.grid.cells.iter()) {
+            assert_eq!(a.state, b.state);
+        }
+    }
+
+    #[test]
+    fn restored_terrain_is_back_in_range() {
+        let grid = Grid::new(6, 4, 16.0, 2.0, Topology::default());
+        let mut snapshot = Snapshot::capture(&World::with_density(grid, 0, 0.0));
+        let impassable = Terrain {
+            attractiveness: 2.0,
+            cost: 1.0,
+            capacity: 1.0,
+        };
+        snapshot.terrain = vec![impassable; 24];
+
+        let world = snapshot.restore(16.0, 2.0).unwrap();
+        let terrain = world.grid.cells[0].terrain;
+        assert_eq!(terrain, Terrain::new(1.0, 0.9, 1.0));
+
+        let path = path::find(&world.grid, CellCoord::new(0, 0), CellCoord::new(3, 5));
+        assert_eq!(path.map(|path| path.len()), Some(8));
+    }
+
+    #[test]
+    fn older_versions_are_migrated_and_newer_ones_refused() {
+        let rules = RuleRegistry::with_life_events();
+        let grid = Grid::new(12, 10, 16.0, 2.0, Topology::default());
+        let mut world = World::new(grid, 5);
+        world.grid.max_times = 3;
+        world.schedule = Schedule::Buffered;
+
+        for _ in 0..10 {
+            world.step(&rules);
+        }
+
+        let mut snapshot = Snapshot::capture(&world);
+        snapshot.version = 1;
+        let json = serde_json::to_string(&snapshot).unwrap();
+
+        // a version 1 world had none of the later fields, whatever the file says
+        let restored = Snapshot::parse(&json).unwrap().restore(16.0, 2.0).unwrap();
+        assert_eq!(restored.grid.max_times, MAX_TIMES);
+        assert_eq!(restored.schedule, Schedule::Sequential);
+        assert!(restored.social.is_empty());
+        assert_eq!(restored.agents.len(), world.agents.len());
+
+        snapshot.version = SNAPSHOT_VERSION + 1;
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(matches!(
+            Snapshot::parse(&json),
+            Err(SnapshotError::Version(_))
+        ));
+        assert!(matches!(
+            snapshot.restore(16.0, 2.0),
+            Err(SnapshotError::Version(_))
+        ));
+    }
+}
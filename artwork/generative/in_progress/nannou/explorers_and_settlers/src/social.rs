@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::agent::AgentId;
+use crate::personality::Traits;
+use crate::registry::AgentRegistry;
+
+// who has met whom, and how many times; undirected, so each pair is stored once
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SocialGraph {
+    edges: HashMap<(AgentId, AgentId), u32>, // the lower id first
+}
+
+impl SocialGraph {
+    pub fn new() -> Self {
+        SocialGraph::default()
+    }
+
+    // rebuild a graph from `edges`, e.g. out of a snapshot
+    pub fn from_edges(edges: impl IntoIterator<Item = (AgentId, AgentId, u32)>) -> Self {
+        let edges = edges
+            .into_iter()
+            .filter(|&(a, b, weight)| a != b && weight > 0)
+            .map(|(a, b, weight)| (Self::key(a, b), weight))
+            .collect();
+
+        SocialGraph { edges }
+    }
+
+    fn key(a: AgentId, b: AgentId) -> (AgentId, AgentId) {
+        (a.min(b), a.max(b))
+    }
+
+    pub fn meet(&mut self, a: AgentId, b: AgentId) {
+        if a != b {
+            *self.edges.entry(Self::key(a, b)).or_default() += 1;
+        }
+    }
+
+    // how many times two agents have met
+    pub fn weight(&self, a: AgentId, b: AgentId) -> u32 {
+        self.edges.get(&Self::key(a, b)).copied().unwrap_or(0)
+    }
+
+    // drop every edge of an agent that left the world
+    pub fn forget(&mut self, id: AgentId) {
+        self.edges.retain(|&(a, b), _| a != id && b != id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    // every pair that met, lower id first, with how often, in id order
+    pub fn edges(&self) -> impl Iterator<Item = (AgentId, AgentId, u32)> {
+        let mut edges: Vec<_> = self
+            .edges
+            .iter()
+            .map(|(&(a, b), &weight)| (a, b, weight))
+            .collect();
+        edges.sort_unstable();
+
+        edges.into_iter()
+    }
+
+    // every agent in the world is a node, even the ones that never met anybody
+    pub fn write_graphml(&self, agents: &AgentRegistry, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            writer,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="settled" for="node" attr.name="settled" attr.type="boolean"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="territory" for="node" attr.name="territory" attr.type="int"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="restlessness" for="node" attr.name="restlessness" attr.type="double"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="sociability" for="node" attr.name="sociability" attr.type="double"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="attachment" for="node" attr.name="attachment" attr.type="double"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="weight" for="edge" attr.name="weight" attr.type="int"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <graph id="encounters" edgedefault="undirected">"#
+        )?;
+
+        for agent in agents.iter() {
+            writeln!(writer, r#"    <node id="{}">"#, agent.id.0)?;
+            writeln!(
+                writer,
+                r#"      <data key="settled">{}</data>"#,
+                agent.is_settled()
+            )?;
+            writeln!(
+                writer,
+                r#"      <data key="territory">{}</data>"#,
+                agents.territory_size(agent.id)
+            )?;
+
+            for (name, value) in Traits::NAMES.iter().zip(agent.traits.values()) {
+                writeln!(writer, r#"      <data key="{}">{}</data>"#, name, value)?;
+            }
+
+            writeln!(writer, "    </node>")?;
+        }
+
+        for (a, b, weight) in self.edges() {
+            writeln!(
+                writer,
+                r#"    <edge source="{}" target="{}"><data key="weight">{}</data></edge>"#,
+                a.0, b.0, weight
+            )?;
+        }
+
+        writeln!(writer, "  </graph>")?;
+        writeln!(writer, "</graphml>")
+    }
+
+    pub fn write_dot(&self, agents: &AgentRegistry, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "graph encounters {{")?;
+
+        for agent in agents.iter() {
+            let shape = if agent.is_settled() { "box" } else { "ellipse" };
+            writeln!(
+                writer,
+                "  {} [label=\"{}\" shape={}];",
+                agent.id.0, agent.id, shape
+            )?;
+        }
+
+        for (a, b, weight) in self.edges() {
+            writeln!(
+                writer,
+                "  {} -- {} [weight={} penwidth={:.2}];",
+                a.0,
+                b.0,
+                weight,
+                1.0 + (weight as f32).ln()
+            )?;
+        }
+
+        writeln!(writer, "}}")
+    }
+
+    pub fn save_graphml(&self, path: impl AsRef<Path>, agents: &AgentRegistry) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_graphml(agents, &mut writer)?;
+        writer.flush()
+    }
+
+    pub fn save_dot(&self, path: impl AsRef<Path>, agents: &AgentRegistry) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_dot(agents, &mut writer)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::Grid;
+    use crate::rules::RuleRegistry;
+    use crate::simulation::World;
+    use crate::topology::Topology;
+
+    #[test]
+    fn explorers_meet_their_neighbours() {
+        let rules = RuleRegistry::with_life_events();
+        let grid = Grid::new(30, 20, 16.0, 2.0, Topology::default());
+        let mut world = World::new(grid, 5);
+
+        for _ in 0..40 {
+            world.step(&rules);
+        }
+
+        assert!(!world.social.is_empty());
+
+        let (a, b, weight) = world.social.edges().next().unwrap();
+        assert!(a < b);
+        assert_eq!(world.social.weight(b, a), weight);
+
+        let mut graphml = Vec::new();
+        world
+            .social
+            .write_graphml(&world.agents, &mut graphml)
+            .unwrap();
+        let graphml = String::from_utf8(graphml).unwrap();
+        assert_eq!(graphml.matches("<node ").count(), world.agents.len());
+        assert_eq!(graphml.matches("<edge ").count(), world.social.len());
+
+        let mut dot = Vec::new();
+        world.social.write_dot(&world.agents, &mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.contains(&format!("{} -- {} [weight={}", a.0, b.0, weight)));
+
+        world.social.forget(a);
+        assert_eq!(world.social.weight(a, b), 0);
+    }
+}
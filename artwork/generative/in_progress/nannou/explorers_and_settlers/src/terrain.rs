@@ -0,0 +1,151 @@
+use std::path::Path;
+
+use nannou::image::{self, ColorType};
+use serde::{Deserialize, Serialize};
+
+use crate::grid::Grid;
+
+// what the land under a cell is like; the default is the same everywhere and changes nothing
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Terrain {
+    pub attractiveness: f32, // 0 to 1, how likely agents are to settle here and head this way
+    pub cost: f32,           // 0 to 0.9, the chance of being held back when moving in
+    pub capacity: f32,       // 0 to 1, the share of the fill cap this cell can take
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        Terrain {
+            attractiveness: 0.5,
+            cost: 0.0,
+            capacity: 1.0,
+        }
+    }
+}
+
+impl Terrain {
+    pub fn new(attractiveness: f32, cost: f32, capacity: f32) -> Self {
+        Terrain {
+            attractiveness: attractiveness.clamp(0.0, 1.0),
+            cost: cost.clamp(0.0, 0.9), // so nothing is impossible to cross
+            capacity: capacity.clamp(0.0, 1.0),
+        }
+    }
+
+    // a bright pixel is attractive, cheap to reach and roomy, a dark one the opposite
+    pub fn from_luma(luma: f32) -> Self {
+        Terrain::new(luma, 1.0 - luma, luma)
+    }
+
+    // red is attractiveness, green is cost and blue is capacity
+    pub fn from_rgb(red: f32, green: f32, blue: f32) -> Self {
+        Terrain::new(red, green, blue)
+    }
+
+    // how many times the cell can be filled before it gets blocked, at least once
+    pub fn max_times(&self, max_times: i32) -> i32 {
+        ((max_times as f32 * self.capacity).round() as i32).max(1)
+    }
+
+    // how much an agent next door wants to head here
+    pub fn pull(&self) -> f32 {
+        (0.05 + self.attractiveness) / (1.0 + self.cost)
+    }
+}
+
+// stretch an image over the grid and read every cell's terrain from the pixel at its center
+pub fn load_map(path: impl AsRef<Path>, grid: &mut Grid) -> image::ImageResult<()> {
+    let image = image::open(path)?;
+    let grayscale = matches!(
+        image.color(),
+        ColorType::L8 | ColorType::La8 | ColorType::L16 | ColorType::La16
+    );
+    let image = image.to_rgb8();
+    let (width, height) = image.dimensions();
+
+    for cell in grid.cells.iter_mut() {
+        let x = (cell.coord.col as f32 + 0.5) / grid.n_cols as f32 * width as f32;
+        // rows go up from the bottom of the window, images go down from the top
+        let y =
+            (grid.n_rows as f32 - cell.coord.row as f32 - 0.5) / grid.n_rows as f32 * height as f32;
+
+        let [red, green, blue] = image.get_pixel(x as u32, y as u32).0;
+        let [red, green, blue] = [red, green, blue].map(|channel| channel as f32 / 255.0);
+
+        cell.terrain = if grayscale {
+            Terrain::from_luma(red)
+        } else {
+            Terrain::from_rgb(red, green, blue)
+        };
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::RuleRegistry;
+    use crate::simulation::World;
+    use crate::topology::{CellCoord, Topology};
+
+    #[test]
+    fn settlements_grow_on_attractive_land() {
+        // bright on the left, dark on the right
+        let path = std::env::temp_dir().join("explorers_and_settlers_terrain.png");
+        image::GrayImage::from_fn(40, 30, |x, _| image::Luma([if x < 20 { 255 } else { 0 }]))
+            .save(&path)
+            .unwrap();
+
+        let mut grid = Grid::new(40, 30, 16.0, 2.0, Topology::default());
+        load_map(&path, &mut grid).unwrap();
+
+        assert_eq!(
+            grid.cell(CellCoord::new(0, 0)).terrain,
+            Terrain::from_luma(1.0)
+        );
+        assert_eq!(
+            grid.cell(CellCoord::new(29, 39)).terrain,
+            Terrain::from_luma(0.0)
+        );
+
+        let rules = RuleRegistry::new();
+        let mut world = World::new(grid, 3);
+
+        for _ in 0..60 {
+            world.step(&rules);
+        }
+
+        let (left, right): (Vec<_>, Vec<_>) = world
+            .grid
+            .cells
+            .iter()
+            .filter(|cell| cell.owner().is_some())
+            .partition(|cell| cell.coord.col < 20);
+
+        assert!(
+            left.len() > right.len(),
+            "{} vs {}",
+            left.len(),
+            right.len()
+        );
+
+        let settled: Vec<_> = world
+            .agents
+            .iter()
+            .filter(|agent| agent.is_settled())
+            .collect();
+        let settled_left = settled
+            .iter()
+            .filter(|agent| agent.position.col < 20)
+            .count();
+        let settled_right = settled.len() - settled_left;
+
+        assert!(
+            settled_left > settled_right * 3,
+            "{} vs {}",
+            settled_left,
+            settled_right
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use crate::chronicle;
+use crate::journal::{Event, Journal};
+use crate::simulation::World;
+use crate::snapshot::{Snapshot, SnapshotError};
+
+// a base snapshot plus every event since, so any epoch in between can be rebuilt
+pub struct Timeline {
+    base: Snapshot,
+    journal: Journal,
+}
+
+impl Timeline {
+    pub fn new(world: &World) -> Self {
+        Timeline {
+            base: Snapshot::capture(world),
+            journal: Journal::new(),
+        }
+    }
+
+    pub fn record(&mut self, epoch: usize, events: Vec<Event>) {
+        self.journal.record(epoch, events);
+    }
+
+    pub fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    pub fn first_epoch(&self) -> usize {
+        self.base.epoch
+    }
+
+    pub fn last_epoch(&self) -> usize {
+        self.journal.last_epoch().unwrap_or(self.base.epoch)
+    }
+
+    // rebuild the world as it was at the end of `epoch`
+    pub fn replay(
+        &self,
+        epoch: usize,
+        cell_size: f32,
+        cell_spacing: f32,
+    ) -> Result<World, SnapshotError> {
+        let mut world = self.base.clone().restore(cell_size, cell_spacing)?;
+        self.advance(&mut world, epoch);
+
+        Ok(world)
+    }
+
+    // apply the events after `world.epoch` up to `epoch` onto an already replayed world
+    pub fn advance(&self, world: &mut World, epoch: usize) {
+        let epoch = epoch.min(self.last_epoch());
+
+        // influence isn't journaled, it spreads again before each epoch's events like it did live
+        for next in world.epoch + 1..=epoch {
+            world.grid.spread_influence();
+
+            for entry in self.journal.between(next - 1, next) {
+                entry.event.apply(world);
+                chronicle::write(world, next, &entry.event);
+            }
+        }
+
+        world.epoch = world.epoch.max(epoch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::CellState;
+    use crate::grid::Grid;
+    use crate::rules::RuleRegistry;
+    use crate::topology::{CellCoord, Topology};
+
+    fn picture(world: &World) -> (Vec<CellState>, Vec<CellCoord>, Vec<f32>) {
+        let cells = world.grid.cells.iter().map(|cell| cell.state.clone());
+        let positions = world.agents.iter().map(|agent| agent.position);
+        let influence = world.grid.influence.values().to_vec();
+
+        (cells.collect(), positions.collect(), influence)
+    }
+
+    #[test]
+    fn replaying_the_journal_rebuilds_every_epoch() {
+        let rules = RuleRegistry::with_life_events();
+        let grid = Grid::new(30, 20, 16.0, 2.0, Topology::default());
+        let mut world = World::new(grid, 5);
+        let mut timeline = Timeline::new(&world);
+        let mut pictures = Vec::new();
+
+        for _ in 0..30 {
+            let events = world.step(&rules);
+            timeline.record(world.epoch, events);
+            pictures.push((world.epoch, picture(&world)));
+        }
+
+        for (epoch, expected) in pictures.iter().rev() {
+            let replayed = timeline.replay(*epoch, 16.0, 2.0).unwrap();
+            assert_eq!(&picture(&replayed), expected, "epoch {}", epoch);
+        }
+    }
+}
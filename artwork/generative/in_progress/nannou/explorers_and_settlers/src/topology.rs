@@ -0,0 +1,129 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct CellCoord {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl CellCoord {
+    pub fn new(row: usize, col: usize) -> Self {
+        CellCoord { row, col }
+    }
+}
+
+impl fmt::Display for CellCoord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({},{})", self.row, self.col)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Neighborhood {
+    VonNeumann, // top, bottom, left and right
+    Moore,      // von neumann plus the four diagonals
+    Hexagonal,  // odd rows are shifted half a cell to the right
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Boundary {
+    Bounded,  // cells outside the grid don't exist
+    Toroidal, // leaving one edge enters from the opposite one
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Topology {
+    pub neighborhood: Neighborhood,
+    pub boundary: Boundary,
+}
+
+const VON_NEUMANN: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, -1), (0, 1)];
+
+const MOORE: [(isize, isize); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 1),
+    (-1, -1),
+    (-1, 1),
+];
+
+const HEX_EVEN_ROW: [(isize, isize); 6] = [(0, -1), (0, 1), (1, -1), (1, 0), (-1, -1), (-1, 0)];
+const HEX_ODD_ROW: [(isize, isize); 6] = [(0, -1), (0, 1), (1, 0), (1, 1), (-1, 0), (-1, 1)];
+
+impl Topology {
+    pub fn new(neighborhood: Neighborhood, boundary: Boundary) -> Self {
+        Topology {
+            neighborhood,
+            boundary,
+        }
+    }
+
+    // a hexagonal torus only lines up when it wraps from an odd row back to an even one
+    pub fn needs_even_rows(&self) -> bool {
+        self.neighborhood == Neighborhood::Hexagonal && self.boundary == Boundary::Toroidal
+    }
+
+    // whether a grid of that size works with this topology, and why not
+    pub fn fits(&self, n_cols: usize, n_rows: usize) -> Result<(), String> {
+        if n_cols == 0 || n_rows == 0 {
+            return Err(format!(
+                "a {}x{} grid has no cells, it needs at least one column and one row",
+                n_cols, n_rows
+            ));
+        }
+
+        if self.needs_even_rows() && n_rows % 2 == 1 {
+            return Err(format!(
+                "a hexagonal torus needs an even number of rows, not {}",
+                n_rows
+            ));
+        }
+
+        Ok(())
+    }
+
+    // (row, col) steps from a cell to each of its neighbors
+    pub fn offsets(&self, coord: CellCoord) -> &'static [(isize, isize)] {
+        match self.neighborhood {
+            Neighborhood::VonNeumann => &VON_NEUMANN,
+            Neighborhood::Moore => &MOORE,
+            Neighborhood::Hexagonal => match coord.row % 2 {
+                0 => &HEX_EVEN_ROW,
+                _ => &HEX_ODD_ROW,
+            },
+        }
+    }
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Topology::new(Neighborhood::VonNeumann, Boundary::Bounded)
+    }
+}
+
+// parses names like "moore", "hexagonal" or "von-neumann-torus"
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, boundary) = match s.strip_suffix("-torus") {
+            Some(name) => (name, Boundary::Toroidal),
+            None => (s, Boundary::Bounded),
+        };
+
+        let neighborhood = match name {
+            "von-neumann" => Neighborhood::VonNeumann,
+            "moore" => Neighborhood::Moore,
+            "hexagonal" => Neighborhood::Hexagonal,
+            _ => return Err(format!("unknown topology: {}", s)),
+        };
+
+        Ok(Topology::new(neighborhood, boundary))
+    }
+}